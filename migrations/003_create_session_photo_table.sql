@@ -0,0 +1,11 @@
+-- Raw captures belonging to a session; a burst produces several, one per shot
+CREATE TABLE IF NOT EXISTS session_photo (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT NOT NULL,
+    shot_index INTEGER NOT NULL,
+    file_name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+-- Create index on session_id for per-session lookups
+CREATE INDEX IF NOT EXISTS idx_session_photo_session_id ON session_photo(session_id);
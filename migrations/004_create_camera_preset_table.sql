@@ -0,0 +1,12 @@
+-- Named camera exposure presets (gphoto2 config widget values stored as a JSON object)
+CREATE TABLE IF NOT EXISTS camera_preset (
+    name TEXT PRIMARY KEY NOT NULL,
+    settings TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL
+);
+
+-- Starting points for the usual venues; adjust from /admin/camera/presets
+INSERT OR IGNORE INTO camera_preset (name, settings, active, updated_at) VALUES
+    ('hall daylight', '{"aperture":"5.6","iso":"400","shutterspeed":"1/125","whitebalance":"Daylight"}', 0, '1970-01-01T00:00:00+00:00'),
+    ('evening tungsten', '{"aperture":"4","iso":"1600","shutterspeed":"1/60","whitebalance":"Tungsten"}', 0, '1970-01-01T00:00:00+00:00');
@@ -0,0 +1,2 @@
+-- Shots rebuilt from a live view frame after the full-resolution capture failed
+ALTER TABLE session_photo ADD COLUMN fallback INTEGER NOT NULL DEFAULT 0;
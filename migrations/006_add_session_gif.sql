@@ -0,0 +1,2 @@
+-- Animated poster recorded from live view, alongside the printed photo
+ALTER TABLE session ADD COLUMN gif_path TEXT;
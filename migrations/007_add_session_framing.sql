@@ -0,0 +1,5 @@
+-- How the session's shots are fitted to the print, set by staff when re-framing.
+-- photo_fit is cover, contain or focal; focal_x/focal_y are fractions of the photo
+ALTER TABLE session ADD COLUMN photo_fit TEXT;
+ALTER TABLE session ADD COLUMN focal_x REAL;
+ALTER TABLE session ADD COLUMN focal_y REAL;
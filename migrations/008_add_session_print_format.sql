@@ -0,0 +1,2 @@
+-- Print format chosen for the session (4x6, 5x7, 2x6, 4x6-2up); NULL uses the event's
+ALTER TABLE session ADD COLUMN print_format TEXT;
@@ -0,0 +1,625 @@
+// Camera abstraction so the booth can run against a real DSLR or a mock backend
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::camera_state::{CameraState, CameraStateMachine};
+use crate::config::{CameraBackend, CameraConfig, RetryPolicy};
+use crate::errors::CameraError;
+use crate::gphoto_camera::GPhotoCamera;
+use crate::orientation::orient_capture;
+use crate::preview_broadcaster::PreviewBroadcaster;
+use crate::process_supervisor::ProcessInfo;
+
+/// Stream of JPEG-encoded live view frames
+pub type PreviewFrames = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+
+/// Camera slot shared between the HTTP handlers and the startup code
+pub type SharedCamera = Arc<Mutex<Option<Arc<dyn Camera>>>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraStatus {
+    pub backend: &'static str,
+    pub model: Option<String>,
+    pub state: CameraState,
+    pub is_connected: bool,
+    pub is_streaming: bool,
+    pub error_message: Option<String>,
+    /// Helper processes the backend has running (ffmpeg for the gphoto2 pipeline)
+    pub processes: Vec<ProcessInfo>,
+}
+
+// Camera trait
+#[async_trait]
+pub trait Camera: Send + Sync {
+    /// Detect and connect to the camera
+    async fn initialize(&self) -> Result<(), String>;
+    /// Whether the camera can currently be detected, without touching the open session
+    async fn is_present(&self) -> bool;
+    /// Start feeding live view frames
+    async fn start_preview(&self) -> Result<(), String>;
+    /// Stop live view so the camera is free for a capture
+    async fn stop_preview(&self) -> Result<(), String>;
+    /// Capture a full-resolution photo, write it to `output_path` and return the JPEG bytes
+    async fn capture_photo(&self, output_path: &str) -> Result<Vec<u8>, CameraError>;
+    async fn status(&self) -> CameraStatus;
+    /// Read a camera setting by its gphoto2 config name (e.g. "iso", "whitebalance")
+    async fn get_config_value(&self, key: &str) -> Result<String, String> {
+        Err(format!(
+            "{} has no config setting '{}'",
+            self.type_name(),
+            key
+        ))
+    }
+    /// Change a camera setting by its gphoto2 config name
+    async fn set_config_value(&self, key: &str, _value: &str) -> Result<(), String> {
+        Err(format!(
+            "{} cannot change config setting '{}'",
+            self.type_name(),
+            key
+        ))
+    }
+    /// How long before the shutter should fire to stop live view and let the camera settle
+    fn prepare_lead_time(&self) -> Duration {
+        Duration::ZERO
+    }
+    /// Open a new stream of live view frames
+    fn preview_frames(&self) -> PreviewFrames;
+    fn type_name(&self) -> &'static str;
+    /// Stop live view and any helper processes, waiting for them to exit. Called once
+    /// at app shutdown, before the camera is dropped
+    async fn shutdown(&self) {}
+}
+
+// Mock camera implementation that replays JPEGs from a directory
+pub struct MockCamera {
+    image_dir: PathBuf,
+    preview_fps: u32,
+    images: Arc<Mutex<Vec<PathBuf>>>,
+    next_capture: AtomicUsize,
+    // Stand-in for the camera's config widgets so presets can be tried without hardware
+    settings: Mutex<BTreeMap<String, String>>,
+    state: Arc<CameraStateMachine>,
+}
+
+impl MockCamera {
+    pub fn new(config: &CameraConfig, state: Arc<CameraStateMachine>) -> Self {
+        MockCamera {
+            image_dir: config.mock_image_dir.clone(),
+            preview_fps: config.mock_preview_fps.max(1),
+            images: Arc::new(Mutex::new(Vec::new())),
+            next_capture: AtomicUsize::new(0),
+            settings: Mutex::new(
+                [
+                    ("aperture", "5.6"),
+                    ("imageformat", "Large Fine JPEG"),
+                    ("iso", "Auto"),
+                    ("shutterspeed", "1/125"),
+                    ("whitebalance", "Auto"),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ),
+            state,
+        }
+    }
+
+    fn scan_images(dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read mock image directory {:?}: {}", dir, e))?;
+
+        let mut images: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+                    .unwrap_or(false)
+            })
+            .collect();
+        images.sort();
+
+        if images.is_empty() {
+            return Err(format!(
+                "No JPEG files found in mock image directory {:?}",
+                dir
+            ));
+        }
+        Ok(images)
+    }
+
+    /// Copy the next image in the rotation to `output_path`
+    async fn capture_next(&self, output_path: &str) -> Result<Vec<u8>, CameraError> {
+        let source = {
+            let images = self.images.lock().unwrap();
+            if images.is_empty() {
+                return Err(CameraError::DeviceNotFound {
+                    device: self.image_dir.display().to_string(),
+                });
+            }
+            let idx = self.next_capture.fetch_add(1, Ordering::SeqCst) % images.len();
+            images[idx].clone()
+        };
+        info!("MockCamera: capturing {:?} to {}", source, output_path);
+
+        // Simulate the shutter and download time
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let jpeg_data = tokio::fs::read(&source).await?;
+        tokio::fs::write(output_path, &jpeg_data).await?;
+
+        Ok(jpeg_data)
+    }
+}
+
+#[async_trait]
+impl Camera for MockCamera {
+    async fn initialize(&self) -> Result<(), String> {
+        info!("Initializing mock camera from {:?}", self.image_dir);
+        let images = Self::scan_images(&self.image_dir).inspect_err(|e| self.state.fail(e))?;
+        info!("Mock camera loaded {} image(s)", images.len());
+        *self.images.lock().unwrap() = images;
+        let _ = self
+            .state
+            .transition(CameraState::Idle, "mock images loaded");
+        Ok(())
+    }
+
+    async fn is_present(&self) -> bool {
+        self.image_dir.is_dir()
+    }
+
+    async fn start_preview(&self) -> Result<(), String> {
+        match self.state.current() {
+            CameraState::Previewing => return Ok(()),
+            CameraState::Error => {
+                self.state
+                    .transition(CameraState::Idle, "retrying after error")?;
+            }
+            _ => {}
+        }
+        self.state
+            .transition(CameraState::Previewing, "preview started")?;
+        info!("MockCamera: preview started");
+        Ok(())
+    }
+
+    async fn stop_preview(&self) -> Result<(), String> {
+        if self.state.current() != CameraState::Previewing {
+            return Ok(());
+        }
+        self.state
+            .transition(CameraState::StoppingPreview, "preview stop requested")?;
+        self.state
+            .transition(CameraState::Idle, "preview stopped")?;
+        info!("MockCamera: preview stopped");
+        Ok(())
+    }
+
+    async fn capture_photo(&self, output_path: &str) -> Result<Vec<u8>, CameraError> {
+        match self.state.current() {
+            CameraState::Previewing => self.stop_preview().await.map_err(CameraError::Busy)?,
+            CameraState::Error => {
+                self.state
+                    .transition(CameraState::Idle, "retrying after error")
+                    .map_err(CameraError::Busy)?;
+            }
+            _ => {}
+        }
+        self.state
+            .transition(CameraState::Capturing, "capture requested")
+            .map_err(CameraError::Busy)?;
+
+        let result = self.capture_next(output_path).await;
+        match &result {
+            Ok(_) => {
+                let _ = self.state.transition(CameraState::Idle, "capture complete");
+            }
+            Err(e) => self.state.fail(&e.to_string()),
+        }
+        result
+    }
+
+    async fn status(&self) -> CameraStatus {
+        let image_count = self.images.lock().unwrap().len();
+        let state = self.state.current();
+        CameraStatus {
+            backend: self.type_name(),
+            model: None,
+            state,
+            is_connected: image_count > 0,
+            is_streaming: state == CameraState::Previewing,
+            error_message: (image_count == 0).then(|| "No mock images loaded".to_string()),
+            processes: Vec::new(),
+        }
+    }
+
+    async fn get_config_value(&self, key: &str) -> Result<String, String> {
+        self.settings
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("Unknown config '{}'", key))
+    }
+
+    async fn set_config_value(&self, key: &str, value: &str) -> Result<(), String> {
+        let mut settings = self.settings.lock().unwrap();
+        let current = settings
+            .get_mut(key)
+            .ok_or_else(|| format!("Unknown config '{}'", key))?;
+        info!("MockCamera: {} = {}", key, value);
+        *current = value.to_string();
+        Ok(())
+    }
+
+    fn preview_frames(&self) -> PreviewFrames {
+        let images = self.images.clone();
+        let state = self.state.clone();
+        let interval = Duration::from_millis(1000 / self.preview_fps as u64);
+
+        Box::pin(async_stream::stream! {
+            let mut ticker = tokio::time::interval(interval);
+            let mut idx = 0usize;
+            loop {
+                ticker.tick().await;
+                if state.current() != CameraState::Previewing {
+                    continue;
+                }
+
+                let path = {
+                    let images = images.lock().unwrap();
+                    if images.is_empty() {
+                        continue;
+                    }
+                    images[idx % images.len()].clone()
+                };
+                idx = idx.wrapping_add(1);
+
+                match tokio::fs::read(&path).await {
+                    Ok(data) => yield Bytes::from(data),
+                    Err(e) => {
+                        warn!("MockCamera: failed to read preview frame {:?}: {}", path, e);
+                        continue;
+                    }
+                }
+                debug!("MockCamera: served preview frame {:?}", path);
+            }
+        })
+    }
+
+    fn type_name(&self) -> &'static str {
+        "Mock Camera (File Replay)"
+    }
+}
+
+/// Capture a photo, retrying transient failures with the policy's backoff
+pub async fn capture_with_retry(
+    camera: &dyn Camera,
+    output_path: &str,
+    policy: &RetryPolicy,
+) -> Result<Vec<u8>, CameraError> {
+    let mut attempt = 1;
+    loop {
+        match camera.capture_photo(output_path).await {
+            Ok(jpeg_data) => {
+                if attempt > 1 {
+                    info!("Capture succeeded on attempt {}", attempt);
+                }
+                return Ok(jpeg_data);
+            }
+            Err(e) if e.is_transient() && attempt < policy.max_attempts => {
+                let delay = policy.backoff(attempt);
+                warn!(
+                    "Capture attempt {}/{} failed ({}), retrying in {:?}",
+                    attempt, policy.max_attempts, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// One photo written to storage by a capture
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedShot {
+    pub file_name: String,
+    /// Rebuilt from a live view frame because the full-resolution capture failed
+    pub fallback: bool,
+}
+
+/// Capture `shots` photos `interval` apart into `dir`, returning them in order.
+/// Live view stays off between shots so the preview pipeline isn't rebuilt each time.
+/// A shot that still fails after its retries is replaced by the last live view frame
+/// from `fallback` when there is a recent one; otherwise the shots taken so far are
+/// removed, since a partial strip is no use. Shots are stored upright and mirrored as
+/// the config says.
+pub async fn capture_shots(
+    camera: &dyn Camera,
+    dir: &Path,
+    shots: u32,
+    interval: Duration,
+    config: &CameraConfig,
+    fallback: Option<&PreviewBroadcaster>,
+) -> Result<Vec<CapturedShot>, CameraError> {
+    let policy = &config.capture_retry;
+    let timestamp = chrono::Utc::now().timestamp();
+    let mut files: Vec<CapturedShot> = Vec::with_capacity(shots as usize);
+
+    for shot in 1..=shots.max(1) {
+        if shot > 1 {
+            tokio::time::sleep(interval).await;
+        }
+
+        let file_name = if shots > 1 {
+            format!("cap_{}_{}.jpg", timestamp, shot)
+        } else {
+            format!("cap_{}.jpg", timestamp)
+        };
+        let save_path = dir.join(&file_name);
+        info!("Shot {}/{} -> {:?}", shot, shots, save_path);
+
+        let captured =
+            match capture_with_retry(camera, save_path.to_str().unwrap_or(""), policy).await {
+                Ok(jpeg_data) => Ok((jpeg_data, false)),
+                Err(e) => fallback_shot(fallback, e).map(|jpeg_data| (jpeg_data, true)),
+            };
+
+        let captured = match captured {
+            Ok((jpeg_data, is_fallback)) => {
+                let orientation = config.orientation;
+                tokio::task::spawn_blocking(move || orient_capture(jpeg_data, &orientation))
+                    .await
+                    .map(|jpeg_data| (jpeg_data, is_fallback))
+                    .map_err(|e| CameraError::CaptureError(format!("orienting shot: {}", e)))
+            }
+            Err(e) => Err(e),
+        };
+
+        let result = match captured {
+            Ok((jpeg_data, is_fallback)) => tokio::fs::write(&save_path, &jpeg_data)
+                .await
+                .map(|_| is_fallback)
+                .map_err(CameraError::from),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(is_fallback) => files.push(CapturedShot {
+                file_name,
+                fallback: is_fallback,
+            }),
+            Err(e) => {
+                warn!("Shot {}/{} failed: {} ({})", shot, shots, e, e.kind());
+                for file in &files {
+                    let _ = std::fs::remove_file(dir.join(&file.file_name));
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Stand in for a failed capture with the last live view frame, or hand the error back
+pub fn fallback_shot(
+    fallback: Option<&PreviewBroadcaster>,
+    error: CameraError,
+) -> Result<Vec<u8>, CameraError> {
+    let Some(preview) = fallback else {
+        return Err(error);
+    };
+
+    match preview.fallback_photo() {
+        Ok(jpeg_data) => {
+            warn!(
+                "Capture failed ({}), using last live view frame instead",
+                error
+            );
+            Ok(jpeg_data)
+        }
+        Err(reason) => {
+            warn!("No live view fallback for failed capture: {}", reason);
+            Err(error)
+        }
+    }
+}
+
+/// Write config widgets to the camera, carrying on past failures. Returns the settings
+/// the camera rejected, with the reason. Used by the admin config endpoint and presets
+/// alike, so both send values the same way
+pub async fn write_settings(
+    camera: &dyn Camera,
+    settings: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut failed = BTreeMap::new();
+    for (key, value) in settings {
+        let value = value.trim();
+        match camera.set_config_value(key, value).await {
+            Ok(()) => info!("  {} = {}", key, value),
+            Err(e) => {
+                warn!("  {} = {} rejected: {}", key, value, e);
+                failed.insert(key.clone(), e);
+            }
+        }
+    }
+    failed
+}
+
+/// Bring live view back after a capture without holding up the caller
+pub fn spawn_preview_restart(camera: Arc<dyn Camera>) {
+    info!("Restarting preview stream after capture");
+    tokio::spawn(async move {
+        if let Err(e) = camera.start_preview().await {
+            warn!("Failed to restart preview stream: {}", e);
+        } else {
+            info!("Preview stream restarted successfully");
+        }
+    });
+}
+
+// Factory function to create the camera backend selected in the config
+pub fn new_camera(
+    config: CameraConfig,
+    state: Arc<CameraStateMachine>,
+) -> Result<Arc<dyn Camera>, String> {
+    match config.backend {
+        CameraBackend::GPhoto => {
+            info!("Using GPhoto2 camera backend");
+            Ok(Arc::new(GPhotoCamera::new(config, state)?))
+        }
+        CameraBackend::Mock => {
+            info!(
+                "Using mock camera backend with images from {:?}",
+                config.mock_image_dir
+            );
+            Ok(Arc::new(MockCamera::new(&config, state)))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::{BurstConfig, OrientationConfig, PreviewStreamConfig, TimingConfig};
+
+    /// Mock camera replaying `dir`, shared by every test that needs a `CameraConfig`
+    pub(crate) fn mock_config(dir: &Path) -> CameraConfig {
+        CameraConfig {
+            backend: CameraBackend::Mock,
+            v4l2_loopback_device: "/dev/video0".to_string(),
+            mock_image_dir: dir.to_path_buf(),
+            mock_preview_fps: 10,
+            reconnect_interval_secs: 5,
+            capture_retry: RetryPolicy {
+                max_attempts: 3,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+            },
+            burst: BurstConfig {
+                shots: 1,
+                interval_secs: 0,
+            },
+            timing: TimingConfig::default(),
+            orientation: OrientationConfig::default(),
+            preview_stream: PreviewStreamConfig::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_camera_captures_in_order() {
+        let dir = std::env::temp_dir().join(format!("mock_camera_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.jpg"), b"first").unwrap();
+        std::fs::write(dir.join("b.jpg"), b"second").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+        let camera = MockCamera::new(&mock_config(&dir), Arc::new(CameraStateMachine::new()));
+        camera.initialize().await.unwrap();
+
+        let out = dir.join("out.jpg");
+        let out_str = out.to_str().unwrap();
+        assert_eq!(camera.capture_photo(out_str).await.unwrap(), b"first");
+        assert_eq!(camera.capture_photo(out_str).await.unwrap(), b"second");
+        assert_eq!(camera.capture_photo(out_str).await.unwrap(), b"first");
+        assert_eq!(std::fs::read(&out).unwrap(), b"first");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    // Fails with a busy error a fixed number of times before succeeding
+    struct FlakyCamera {
+        failures_left: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Camera for FlakyCamera {
+        async fn initialize(&self) -> Result<(), String> {
+            Ok(())
+        }
+        async fn is_present(&self) -> bool {
+            true
+        }
+        async fn start_preview(&self) -> Result<(), String> {
+            Ok(())
+        }
+        async fn stop_preview(&self) -> Result<(), String> {
+            Ok(())
+        }
+        async fn capture_photo(&self, _output_path: &str) -> Result<Vec<u8>, CameraError> {
+            if self.failures_left.load(Ordering::SeqCst) > 0 {
+                self.failures_left.fetch_sub(1, Ordering::SeqCst);
+                return Err(CameraError::Busy("I/O in progress".to_string()));
+            }
+            Ok(b"photo".to_vec())
+        }
+        async fn status(&self) -> CameraStatus {
+            CameraStatus {
+                backend: self.type_name(),
+                model: None,
+                state: CameraState::Idle,
+                is_connected: true,
+                is_streaming: false,
+                error_message: None,
+                processes: Vec::new(),
+            }
+        }
+        fn preview_frames(&self) -> PreviewFrames {
+            Box::pin(futures::stream::empty())
+        }
+        fn type_name(&self) -> &'static str {
+            "Flaky"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_retries_transient_errors() {
+        let policy = mock_config(Path::new("/tmp")).capture_retry;
+
+        let camera = FlakyCamera {
+            failures_left: AtomicUsize::new(2),
+        };
+        assert_eq!(
+            capture_with_retry(&camera, "unused.jpg", &policy)
+                .await
+                .unwrap(),
+            b"photo"
+        );
+
+        let camera = FlakyCamera {
+            failures_left: AtomicUsize::new(3),
+        };
+        let err = capture_with_retry(&camera, "unused.jpg", &policy)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), "busy");
+    }
+
+    #[tokio::test]
+    async fn test_mock_camera_requires_images() {
+        let dir = std::env::temp_dir().join(format!("mock_camera_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let camera = MockCamera::new(&mock_config(&dir), Arc::new(CameraStateMachine::new()));
+        assert!(camera.initialize().await.is_err());
+        let status = camera.status().await;
+        assert!(!status.is_connected);
+        assert_eq!(status.state, CameraState::Error);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,227 @@
+// Named sets of camera config values (exposure, white balance) for different venue lighting
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use std::collections::BTreeMap;
+use tracing::info;
+
+use crate::camera::{write_settings, Camera};
+use crate::errors::{AppResult, DatabaseError};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraPreset {
+    pub name: String,
+    /// Config widget name -> value, e.g. "iso" -> "400"
+    pub settings: BTreeMap<String, String>,
+    /// The active preset is applied whenever the camera connects
+    pub active: bool,
+    pub updated_at: String,
+}
+
+#[derive(FromRow)]
+struct CameraPresetRow {
+    name: String,
+    settings: String,
+    active: i32,
+    updated_at: String,
+}
+
+impl TryFrom<CameraPresetRow> for CameraPreset {
+    type Error = DatabaseError;
+
+    fn try_from(row: CameraPresetRow) -> Result<Self, Self::Error> {
+        let settings = serde_json::from_str(&row.settings).map_err(|e| {
+            DatabaseError::QueryFailed(format!("Invalid settings for preset '{}': {}", row.name, e))
+        })?;
+        Ok(CameraPreset {
+            name: row.name,
+            settings,
+            active: row.active != 0,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+impl CameraPreset {
+    pub fn new(name: &str, settings: BTreeMap<String, String>) -> Self {
+        Self {
+            name: name.to_string(),
+            settings,
+            active: false,
+            updated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Insert the preset, or replace the settings of an existing one with the same name
+    pub async fn save(&self, pool: &SqlitePool) -> AppResult<()> {
+        let settings = serde_json::to_string(&self.settings).map_err(|e| {
+            DatabaseError::QueryFailed(format!("Failed to encode preset settings: {}", e))
+        })?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO camera_preset (name, settings, active, updated_at)
+            VALUES (?1, ?2, 0, ?3)
+            ON CONFLICT(name) DO UPDATE SET
+                settings = excluded.settings,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&self.name)
+        .bind(settings)
+        .bind(&self.updated_at)
+        .execute(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(format!("Failed to save camera preset: {}", e)))?;
+
+        Ok(())
+    }
+
+    pub async fn load(name: &str, pool: &SqlitePool) -> AppResult<Option<Self>> {
+        let row = sqlx::query_as::<_, CameraPresetRow>(
+            "SELECT name, settings, active, updated_at FROM camera_preset WHERE name = ?1",
+        )
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(format!("Failed to load camera preset: {}", e)))?;
+
+        Ok(row.map(CameraPreset::try_from).transpose()?)
+    }
+
+    pub async fn list(pool: &SqlitePool) -> AppResult<Vec<Self>> {
+        let rows = sqlx::query_as::<_, CameraPresetRow>(
+            "SELECT name, settings, active, updated_at FROM camera_preset ORDER BY name",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(format!("Failed to list camera presets: {}", e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(CameraPreset::try_from)
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// The preset to apply on camera init, if one has been chosen
+    pub async fn active(pool: &SqlitePool) -> AppResult<Option<Self>> {
+        let row = sqlx::query_as::<_, CameraPresetRow>(
+            "SELECT name, settings, active, updated_at FROM camera_preset WHERE active = 1 LIMIT 1",
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            DatabaseError::QueryFailed(format!("Failed to load active camera preset: {}", e))
+        })?;
+
+        Ok(row.map(CameraPreset::try_from).transpose()?)
+    }
+
+    /// Make `name` the only active preset. Returns false if there is no such preset
+    pub async fn activate(name: &str, pool: &SqlitePool) -> AppResult<bool> {
+        let mut tx = pool.begin().await.map_err(|e| {
+            DatabaseError::TransactionFailed(format!("Failed to start transaction: {}", e))
+        })?;
+
+        sqlx::query("UPDATE camera_preset SET active = 0 WHERE active = 1")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                DatabaseError::QueryFailed(format!("Failed to clear active preset: {}", e))
+            })?;
+
+        let result = sqlx::query("UPDATE camera_preset SET active = 1 WHERE name = ?1")
+            .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                DatabaseError::QueryFailed(format!("Failed to activate camera preset: {}", e))
+            })?;
+
+        if result.rows_affected() == 0 {
+            // Leave the previous choice alone
+            return Ok(false);
+        }
+
+        tx.commit().await.map_err(|e| {
+            DatabaseError::TransactionFailed(format!("Failed to commit active preset: {}", e))
+        })?;
+
+        Ok(true)
+    }
+
+    /// Returns false if there is no such preset
+    pub async fn delete(name: &str, pool: &SqlitePool) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM camera_preset WHERE name = ?1")
+            .bind(name)
+            .execute(pool)
+            .await
+            .map_err(|e| {
+                DatabaseError::QueryFailed(format!("Failed to delete camera preset: {}", e))
+            })?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Push every setting to the camera, carrying on past failures.
+    /// Returns the settings the camera rejected, with the reason
+    pub async fn apply(&self, camera: &dyn Camera) -> BTreeMap<String, String> {
+        info!("Applying camera preset '{}'", self.name);
+        write_settings(camera, &self.settings).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection so every query sees the same in-memory database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_only_one_preset_active() {
+        let pool = test_pool().await;
+
+        // Seeded by the migration
+        assert!(CameraPreset::load("hall daylight", &pool)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(CameraPreset::active(&pool).await.unwrap().is_none());
+
+        let settings = BTreeMap::from([("iso".to_string(), "3200".to_string())]);
+        CameraPreset::new("late night", settings)
+            .save(&pool)
+            .await
+            .unwrap();
+
+        assert!(CameraPreset::activate("hall daylight", &pool)
+            .await
+            .unwrap());
+        assert!(CameraPreset::activate("late night", &pool).await.unwrap());
+        assert!(!CameraPreset::activate("missing", &pool).await.unwrap());
+
+        let active = CameraPreset::active(&pool).await.unwrap().unwrap();
+        assert_eq!(active.name, "late night");
+        assert_eq!(active.settings["iso"], "3200");
+        assert_eq!(
+            CameraPreset::list(&pool)
+                .await
+                .unwrap()
+                .iter()
+                .filter(|p| p.active)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,228 @@
+// Camera lifecycle state machine shared by the camera backends and the status endpoint
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+// Number of transitions kept for the status endpoint
+const HISTORY_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraState {
+    /// No camera session is open
+    Disconnected,
+    /// Connected, live view off
+    Idle,
+    /// Live view frames are flowing
+    Previewing,
+    /// Live view is shutting down ahead of a capture
+    StoppingPreview,
+    /// A full-resolution capture is in progress
+    Capturing,
+    /// Reconnecting or restarting after a failure
+    Recovering,
+    /// Last operation failed; waiting for recovery
+    Error,
+}
+
+impl CameraState {
+    /// Whether the machine may move from `self` to `next`
+    pub fn can_transition_to(self, next: CameraState) -> bool {
+        use CameraState::*;
+
+        if self == next {
+            return false;
+        }
+
+        match (self, next) {
+            // A failure or unplug can happen at any point
+            (_, Error) | (_, Disconnected) => true,
+            (Disconnected, Idle) | (Disconnected, Recovering) => true,
+            (Idle, Previewing) | (Idle, Capturing) | (Idle, Recovering) => true,
+            (Previewing, StoppingPreview) | (Previewing, Recovering) => true,
+            (StoppingPreview, Idle) => true,
+            (Capturing, Idle) | (Capturing, Recovering) => true,
+            (Recovering, Idle) | (Recovering, Previewing) => true,
+            (Error, Recovering) | (Error, Idle) => true,
+            _ => false,
+        }
+    }
+
+    /// States in which the camera is up and can take a photo; in any other the kiosk
+    /// tells guests it isn't ready yet
+    pub fn is_ready(self) -> bool {
+        matches!(self, CameraState::Previewing | CameraState::Capturing)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateTransition {
+    pub from: CameraState,
+    pub to: CameraState,
+    pub at: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraStateSnapshot {
+    pub state: CameraState,
+    pub since: String,
+    pub state_ms: i64,
+    pub ready: bool,
+    pub last_error: Option<String>,
+    pub transitions: Vec<StateTransition>,
+}
+
+struct StateInner {
+    state: CameraState,
+    since: DateTime<Utc>,
+    last_error: Option<String>,
+    history: VecDeque<StateTransition>,
+}
+
+pub struct CameraStateMachine {
+    inner: Mutex<StateInner>,
+}
+
+impl CameraStateMachine {
+    pub fn new() -> Self {
+        CameraStateMachine {
+            inner: Mutex::new(StateInner {
+                state: CameraState::Disconnected,
+                since: Utc::now(),
+                last_error: None,
+                history: VecDeque::with_capacity(HISTORY_LEN),
+            }),
+        }
+    }
+
+    pub fn current(&self) -> CameraState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Move to `next`, rejecting transitions the lifecycle doesn't allow
+    pub fn transition(&self, next: CameraState, reason: &str) -> Result<CameraState, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let from = inner.state;
+
+        if !from.can_transition_to(next) {
+            warn!(
+                "Rejected camera state transition {:?} -> {:?} ({})",
+                from, next, reason
+            );
+            return Err(format!(
+                "Invalid camera state transition from {:?} to {:?}",
+                from, next
+            ));
+        }
+
+        let now = Utc::now();
+        debug!(
+            "Camera state {:?} -> {:?} after {}ms ({})",
+            from,
+            next,
+            (now - inner.since).num_milliseconds(),
+            reason
+        );
+
+        inner.state = next;
+        inner.since = now;
+        if next == CameraState::Error {
+            inner.last_error = Some(reason.to_string());
+        }
+        if inner.history.len() == HISTORY_LEN {
+            inner.history.pop_front();
+        }
+        inner.history.push_back(StateTransition {
+            from,
+            to: next,
+            at: now.to_rfc3339(),
+            reason: reason.to_string(),
+        });
+
+        Ok(from)
+    }
+
+    /// Record a failure, moving to Error. Further failures while already in Error
+    /// replace the recorded error, so the status shows the latest one
+    pub fn fail(&self, reason: &str) {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.state == CameraState::Error {
+                debug!("Camera still in error: {}", reason);
+                inner.last_error = Some(reason.to_string());
+                return;
+            }
+        }
+        let _ = self.transition(CameraState::Error, reason);
+    }
+
+    pub fn snapshot(&self) -> CameraStateSnapshot {
+        let inner = self.inner.lock().unwrap();
+        CameraStateSnapshot {
+            state: inner.state,
+            since: inner.since.to_rfc3339(),
+            state_ms: (Utc::now() - inner.since).num_milliseconds(),
+            ready: inner.state.is_ready(),
+            last_error: inner.last_error.clone(),
+            transitions: inner.history.iter().cloned().collect(),
+        }
+    }
+}
+
+impl Default for CameraStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_cycle_transitions() {
+        let machine = CameraStateMachine::new();
+        assert_eq!(machine.current(), CameraState::Disconnected);
+
+        for next in [
+            CameraState::Idle,
+            CameraState::Previewing,
+            CameraState::StoppingPreview,
+            CameraState::Idle,
+            CameraState::Capturing,
+            CameraState::Idle,
+        ] {
+            machine.transition(next, "test").unwrap();
+        }
+
+        let snapshot = machine.snapshot();
+        assert_eq!(snapshot.state, CameraState::Idle);
+        assert_eq!(snapshot.transitions.len(), 6);
+        assert!(snapshot.last_error.is_none());
+    }
+
+    #[test]
+    fn test_invalid_transition_rejected() {
+        let machine = CameraStateMachine::new();
+        assert!(machine
+            .transition(CameraState::Capturing, "no camera")
+            .is_err());
+        assert_eq!(machine.current(), CameraState::Disconnected);
+
+        machine.transition(CameraState::Idle, "connected").unwrap();
+        machine.fail("usb error");
+        let snapshot = machine.snapshot();
+        assert_eq!(snapshot.state, CameraState::Error);
+        assert_eq!(snapshot.last_error.as_deref(), Some("usb error"));
+        assert!(!snapshot.ready);
+
+        machine.fail("usb timeout");
+        let snapshot = machine.snapshot();
+        assert_eq!(snapshot.last_error.as_deref(), Some("usb timeout"));
+        assert_eq!(snapshot.transitions.len(), 2);
+    }
+}
@@ -0,0 +1,257 @@
+// Hot-plug supervisor: re-detects the camera, reconnects after an unplug or sleep,
+// and publishes connect/disconnect events
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::camera::{new_camera, Camera, SharedCamera};
+use crate::camera_preset::CameraPreset;
+use crate::camera_state::{CameraState, CameraStateMachine};
+use crate::config::CameraConfig;
+
+// Events are only interesting live; late subscribers just miss old ones
+const EVENT_BUFFER: usize = 16;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CameraEvent {
+    Connected {
+        at: String,
+        backend: &'static str,
+        model: Option<String>,
+    },
+    Disconnected {
+        at: String,
+        reason: String,
+    },
+}
+
+pub type CameraEvents = broadcast::Sender<CameraEvent>;
+
+pub fn camera_events() -> CameraEvents {
+    broadcast::channel(EVENT_BUFFER).0
+}
+
+/// Everything the supervisor needs to (re)connect the camera
+#[derive(Clone)]
+pub struct CameraSupervisor {
+    config: CameraConfig,
+    camera: SharedCamera,
+    state: Arc<CameraStateMachine>,
+    events: CameraEvents,
+    // Where the active camera preset lives; without it presets are skipped
+    db_pool: Option<SqlitePool>,
+}
+
+impl CameraSupervisor {
+    pub fn new(
+        config: CameraConfig,
+        camera: SharedCamera,
+        state: Arc<CameraStateMachine>,
+        events: CameraEvents,
+    ) -> Self {
+        CameraSupervisor {
+            config,
+            camera,
+            state,
+            events,
+            db_pool: None,
+        }
+    }
+
+    /// Apply the active camera preset every time the camera connects
+    pub fn with_presets(mut self, db_pool: SqlitePool) -> Self {
+        self.db_pool = Some(db_pool);
+        self
+    }
+
+    async fn apply_active_preset(&self, camera: &dyn Camera) {
+        let Some(pool) = &self.db_pool else {
+            return;
+        };
+
+        match CameraPreset::active(pool).await {
+            Ok(Some(preset)) => {
+                let failed = preset.apply(camera).await;
+                if !failed.is_empty() {
+                    warn!(
+                        "Camera preset '{}' partly applied, rejected: {:?}",
+                        preset.name, failed
+                    );
+                }
+            }
+            Ok(None) => debug!("No active camera preset"),
+            Err(e) => warn!("Failed to load active camera preset: {}", e),
+        }
+    }
+
+    /// Detect, initialize and store the camera, then start its preview
+    pub async fn connect(&self) -> Result<(), String> {
+        let camera = new_camera(self.config.clone(), self.state.clone())?;
+
+        // Cheap presence check first so an absent camera doesn't churn the state history
+        if !camera.is_present().await {
+            return Err("No camera detected".to_string());
+        }
+
+        let _ = self
+            .state
+            .transition(CameraState::Recovering, "camera detected");
+
+        if let Err(e) = camera.initialize().await {
+            let _ = self
+                .state
+                .transition(CameraState::Disconnected, "initialization failed");
+            return Err(format!(
+                "Failed to initialize {}: {}",
+                camera.type_name(),
+                e
+            ));
+        }
+
+        info!("{} initialized successfully", camera.type_name());
+
+        self.apply_active_preset(camera.as_ref()).await;
+
+        // Store camera reference
+        {
+            let mut guard = self.camera.lock().unwrap();
+            *guard = Some(camera.clone());
+        }
+
+        let status = camera.status().await;
+        let _ = self.events.send(CameraEvent::Connected {
+            at: Utc::now().to_rfc3339(),
+            backend: status.backend,
+            model: status.model,
+        });
+
+        // Start preview stream in background
+        tokio::spawn(async move {
+            info!("Starting camera preview stream");
+            if let Err(e) = camera.start_preview().await {
+                error!("Camera stream error: {}", e);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Forget the current camera and tell listeners it's gone
+    fn disconnect(&self, reason: &str) {
+        let camera = self.camera.lock().unwrap().take();
+        if let Some(camera) = camera {
+            warn!("{} disconnected: {}", camera.type_name(), reason);
+        }
+
+        let _ = self.state.transition(CameraState::Disconnected, reason);
+        let _ = self.events.send(CameraEvent::Disconnected {
+            at: Utc::now().to_rfc3339(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Periodically check the camera, reconnecting or restarting the preview as needed
+    pub fn spawn(self) -> JoinHandle<()> {
+        let interval = Duration::from_secs(self.config.reconnect_interval_secs.max(1));
+        info!("Camera supervisor checking every {:?}", interval);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick fires immediately; startup already made the first attempt
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let current = self.camera.lock().unwrap().clone();
+                let Some(camera) = current else {
+                    match self.connect().await {
+                        Ok(()) => info!("Camera reconnected"),
+                        Err(e) => debug!("Camera still unavailable: {}", e),
+                    }
+                    continue;
+                };
+
+                if !camera.is_present().await {
+                    drop(camera);
+                    self.disconnect("camera no longer detected");
+                    continue;
+                }
+
+                // Give the capture path a chance to recover on its own before stepping in
+                let snapshot = self.state.snapshot();
+                if snapshot.state == CameraState::Error
+                    && snapshot.state_ms >= interval.as_millis() as i64
+                {
+                    warn!(
+                        "Camera stuck in error ({}), restarting preview",
+                        snapshot.last_error.unwrap_or_default()
+                    );
+                    if self
+                        .state
+                        .transition(CameraState::Recovering, "supervisor restarting preview")
+                        .is_ok()
+                    {
+                        if let Err(e) = camera.start_preview().await {
+                            self.state.fail(&e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::tests::mock_config;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_connect_publishes_event_once_camera_appears() {
+        let dir = std::env::temp_dir().join(format!("supervisor_{}", uuid::Uuid::new_v4()));
+        let config = CameraConfig {
+            reconnect_interval_secs: 1,
+            ..mock_config(&dir)
+        };
+
+        let camera: SharedCamera = Arc::new(Mutex::new(None));
+        let state = Arc::new(CameraStateMachine::new());
+        let events = camera_events();
+        let mut receiver = events.subscribe();
+        let supervisor = CameraSupervisor::new(config, camera.clone(), state.clone(), events);
+
+        // Nothing plugged in yet: degraded, no state churn
+        assert!(supervisor.connect().await.is_err());
+        assert!(camera.lock().unwrap().is_none());
+        assert_eq!(state.current(), CameraState::Disconnected);
+        assert!(state.snapshot().transitions.is_empty());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.jpg"), b"frame").unwrap();
+        supervisor.connect().await.unwrap();
+        assert!(camera.lock().unwrap().is_some());
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            CameraEvent::Connected { .. }
+        ));
+
+        supervisor.disconnect("unplugged");
+        assert!(camera.lock().unwrap().is_none());
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            CameraEvent::Disconnected { .. }
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,352 @@
+// Server-side capture countdown: the server owns the clock, stops live view ahead of time
+// and fires the shutter on zero, so camera latency no longer adds to the countdown
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::camera::{capture_shots, spawn_preview_restart, CapturedShot, SharedCamera};
+use crate::config::Config;
+use crate::errors::CameraError;
+use crate::preview_broadcaster::PreviewBroadcaster;
+use crate::session::SessionPhoto;
+
+// Countdown events are only interesting live
+const EVENT_BUFFER: usize = 16;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CountdownEvent {
+    /// Seconds left until the shutter; 0 is sent as it fires
+    Tick {
+        capture_id: String,
+        remaining_secs: u64,
+    },
+    /// Live view is being stopped so the camera is ready on zero
+    Preparing { capture_id: String },
+    Captured {
+        capture_id: String,
+        session_id: Option<String>,
+        files: Vec<CapturedShot>,
+        file: String,
+        path: String,
+        redirect: String,
+        /// At least one shot is an upscaled live view frame
+        fallback_quality: bool,
+    },
+    Failed {
+        capture_id: String,
+        error: String,
+        error_kind: &'static str,
+        retryable: bool,
+    },
+}
+
+/// What the kiosk needs to render the countdown against the server's clock
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledCapture {
+    pub capture_id: String,
+    pub target_at: String,
+    pub target_epoch_ms: i64,
+    pub countdown_secs: u64,
+    pub shots: u32,
+}
+
+pub struct ScheduleRequest {
+    pub session_id: Option<String>,
+    pub countdown_secs: u64,
+    pub shots: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("camera not available")]
+    CameraUnavailable,
+    #[error("a capture is already scheduled")]
+    AlreadyScheduled,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Tick(u64),
+    Prepare,
+    Fire,
+}
+
+pub struct CaptureScheduler {
+    camera: SharedCamera,
+    db_pool: SqlitePool,
+    // Source of the live view frame used when a capture fails
+    preview: Arc<PreviewBroadcaster>,
+    events: broadcast::Sender<CountdownEvent>,
+    // One countdown at a time; the booth has a single camera
+    running: Arc<AtomicBool>,
+}
+
+impl CaptureScheduler {
+    pub fn new(
+        camera: SharedCamera,
+        db_pool: SqlitePool,
+        preview: Arc<PreviewBroadcaster>,
+    ) -> Self {
+        CaptureScheduler {
+            camera,
+            db_pool,
+            preview,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CountdownEvent> {
+        self.events.subscribe()
+    }
+
+    /// Start a countdown; the capture runs in the background and reports over the events
+    pub fn schedule(
+        &self,
+        config: &Config,
+        request: ScheduleRequest,
+    ) -> Result<ScheduledCapture, ScheduleError> {
+        let Some(camera) = self.camera.lock().unwrap().clone() else {
+            return Err(ScheduleError::CameraUnavailable);
+        };
+
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(ScheduleError::AlreadyScheduled);
+        }
+
+        let capture_id = uuid::Uuid::new_v4().to_string();
+        let countdown = Duration::from_secs(request.countdown_secs);
+        let lead = config
+            .countdown
+            .prepare_lead_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| camera.prepare_lead_time())
+            .min(countdown);
+
+        let now = Instant::now();
+        let target = now + countdown;
+        let target_at = Utc::now() + chrono::Duration::from_std(countdown).unwrap_or_default();
+
+        // Ticks on each whole second, the early preview stop, then the shutter
+        let mut steps: Vec<(Instant, Step)> = (1..=request.countdown_secs)
+            .map(|remaining| {
+                (
+                    target - Duration::from_secs(remaining),
+                    Step::Tick(remaining),
+                )
+            })
+            .collect();
+        steps.push((target - lead, Step::Prepare));
+        steps.push((target, Step::Tick(0)));
+        steps.push((target, Step::Fire));
+        steps.sort();
+
+        info!(
+            "Capture {} scheduled in {:?} ({} shot(s), preview stops {:?} early)",
+            capture_id, countdown, request.shots, lead
+        );
+
+        let scheduled = ScheduledCapture {
+            capture_id: capture_id.clone(),
+            target_at: target_at.to_rfc3339(),
+            target_epoch_ms: target_at.timestamp_millis(),
+            countdown_secs: request.countdown_secs,
+            shots: request.shots,
+        };
+
+        let events = self.events.clone();
+        let running = self.running.clone();
+        let camera_slot = self.camera.clone();
+        let db_pool = self.db_pool.clone();
+        let preview = self.preview.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            // Frees the scheduler even if the capture panics
+            let _running = RunningGuard(running);
+            for (at, step) in steps {
+                tokio::time::sleep_until(at).await;
+                match step {
+                    Step::Tick(remaining_secs) => {
+                        let _ = events.send(CountdownEvent::Tick {
+                            capture_id: capture_id.clone(),
+                            remaining_secs,
+                        });
+                    }
+                    Step::Prepare => {
+                        let _ = events.send(CountdownEvent::Preparing {
+                            capture_id: capture_id.clone(),
+                        });
+                        let camera = camera_slot.lock().unwrap().clone();
+                        if let Some(camera) = camera {
+                            if let Err(e) = camera.stop_preview().await {
+                                // capture_photo will try again on its own
+                                warn!("Could not stop preview ahead of capture: {}", e);
+                            }
+                        }
+                    }
+                    Step::Fire => {
+                        let event = fire(
+                            &capture_id,
+                            &camera_slot,
+                            &db_pool,
+                            &preview,
+                            &config,
+                            request.session_id.clone(),
+                            request.shots,
+                        )
+                        .await;
+                        let _ = events.send(event);
+                    }
+                }
+            }
+        });
+
+        Ok(scheduled)
+    }
+}
+
+/// Clears the scheduler's running flag when the countdown task ends, however it ends
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Take the shots on zero and describe the outcome as the final countdown event
+async fn fire(
+    capture_id: &str,
+    camera_slot: &SharedCamera,
+    db_pool: &SqlitePool,
+    preview: &PreviewBroadcaster,
+    config: &Config,
+    session_id: Option<String>,
+    shots: u32,
+) -> CountdownEvent {
+    let failed = |e: CameraError| CountdownEvent::Failed {
+        capture_id: capture_id.to_string(),
+        error: e.to_string(),
+        error_kind: e.kind(),
+        retryable: e.is_transient(),
+    };
+
+    // The camera may have been unplugged during the countdown
+    let Some(camera) = camera_slot.lock().unwrap().clone() else {
+        return failed(CameraError::DeviceNotFound {
+            device: "camera not initialized".to_string(),
+        });
+    };
+
+    std::fs::create_dir_all(&config.storage.base_path).ok();
+    let result = capture_shots(
+        camera.as_ref(),
+        &config.storage.base_path,
+        shots,
+        Duration::from_secs(config.camera.burst.interval_secs),
+        &config.camera,
+        Some(preview),
+    )
+    .await;
+    spawn_preview_restart(camera);
+
+    let files = match result {
+        Ok(files) => files,
+        Err(e) => {
+            error!(
+                "Scheduled capture {} failed: {} ({})",
+                capture_id,
+                e,
+                e.kind()
+            );
+            return failed(e);
+        }
+    };
+
+    if let Some(session_id) = &session_id {
+        if let Err(e) = SessionPhoto::replace_all(session_id, &files, db_pool).await {
+            warn!("Failed to attach photos to session {}: {}", session_id, e);
+        }
+    }
+
+    info!("Scheduled capture {} done: {:?}", capture_id, files);
+    let Some(first) = files.first().map(|shot| shot.file_name.clone()) else {
+        return failed(CameraError::CaptureError(
+            "no shots were captured".to_string(),
+        ));
+    };
+    CountdownEvent::Captured {
+        capture_id: capture_id.to_string(),
+        session_id,
+        path: format!("/images/{}", first),
+        redirect: format!("/photo?file={}", first),
+        file: first,
+        fallback_quality: files.iter().any(|shot| shot.fallback),
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, MockCamera};
+    use crate::camera_state::CameraStateMachine;
+    use crate::config::tests::test_config;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_countdown_ends_in_capture() {
+        let dir = std::env::temp_dir().join(format!("scheduler_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("mock")).unwrap();
+        std::fs::write(dir.join("mock").join("a.jpg"), b"shot").unwrap();
+
+        let config = test_config(&dir);
+
+        let camera = MockCamera::new(&config.camera, Arc::new(CameraStateMachine::new()));
+        camera.initialize().await.unwrap();
+        let camera: SharedCamera = Arc::new(Mutex::new(Some(Arc::new(camera))));
+        let pool = SqlitePoolOptions::new()
+            .connect_lazy("sqlite::memory:")
+            .unwrap();
+
+        let preview = Arc::new(PreviewBroadcaster::new(camera.clone()));
+        let scheduler = CaptureScheduler::new(camera, pool, preview);
+        let mut events = scheduler.subscribe();
+        let request = || ScheduleRequest {
+            session_id: None,
+            countdown_secs: 1,
+            shots: 1,
+        };
+
+        let scheduled = scheduler.schedule(&config, request()).unwrap();
+        assert!(scheduler.schedule(&config, request()).is_err());
+
+        let mut ticks = Vec::new();
+        loop {
+            match events.recv().await.unwrap() {
+                CountdownEvent::Tick { remaining_secs, .. } => ticks.push(remaining_secs),
+                CountdownEvent::Preparing { .. } => {}
+                CountdownEvent::Captured {
+                    capture_id, file, ..
+                } => {
+                    assert_eq!(capture_id, scheduled.capture_id);
+                    assert_eq!(std::fs::read(dir.join(file)).unwrap(), b"shot");
+                    break;
+                }
+                CountdownEvent::Failed { error, .. } => panic!("capture failed: {}", error),
+            }
+        }
+        assert_eq!(ticks, vec![1, 0]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
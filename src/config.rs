@@ -1,6 +1,14 @@
 use serde::Deserialize;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::fonts::DEFAULT_FONT_DIRS;
+use crate::photo_effects::{self, PhotoEffect};
+use crate::print_format::PrintFormat;
+use crate::template_layout::{Collage, LayoutCache, TemplateLayout};
+use crate::templates::{parse_hex_color, PhotoFit};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -10,6 +18,9 @@ pub struct Config {
     pub printer: PrinterConfig,
     pub template: TemplateConfig,
     pub database: DatabaseConfig,
+    pub countdown: CountdownConfig,
+    pub watchdog: WatchdogConfig,
+    pub gif: GifConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -18,9 +29,198 @@ pub struct ServerConfig {
     pub port: u16,
 }
 
+// Bursts share the photo area as a grid; beyond six shots (3x2) faces get too small
+pub const MAX_BURST_SHOTS: u32 = 6;
+// The live view pipeline never delivers more than this
+pub const MAX_PREVIEW_FPS: u32 = 30;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct CameraConfig {
+    pub backend: CameraBackend,
     pub v4l2_loopback_device: String,
+    /// Directory of JPEGs served by the mock camera
+    pub mock_image_dir: PathBuf,
+    pub mock_preview_fps: u32,
+    /// How often the supervisor re-detects the camera
+    pub reconnect_interval_secs: u64,
+    pub capture_retry: RetryPolicy,
+    pub burst: BurstConfig,
+    pub timing: TimingConfig,
+    pub orientation: OrientationConfig,
+    pub preview_stream: PreviewStreamConfig,
+}
+
+/// What /preview sends clients that don't ask for their own rate, size or quality
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PreviewStreamConfig {
+    /// Frames per second per client; frames beyond this are skipped, not queued
+    pub fps: u32,
+    /// Scale frames down to this width; None keeps the camera's resolution
+    pub width: Option<u32>,
+    /// Re-encode frames at this JPEG quality (1-100); None passes them through
+    pub quality: Option<u8>,
+}
+
+impl Default for PreviewStreamConfig {
+    fn default() -> Self {
+        PreviewStreamConfig {
+            fps: MAX_PREVIEW_FPS,
+            width: None,
+            quality: None,
+        }
+    }
+}
+
+/// How the camera is mounted and which outputs are mirrored
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct OrientationConfig {
+    /// Clockwise rotation for captures and live view: 0, 90, 180 or 270
+    pub rotation: u16,
+    /// Flip live view so guests see themselves as in a mirror
+    pub mirror_preview: bool,
+    /// Flip the captured photos (and the animated poster)
+    pub mirror_capture: bool,
+}
+
+/// Delays the gphoto2 backend waits out while the camera switches modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct CameraTiming {
+    /// After stopping live view, before triggering the shutter
+    pub post_preview_stop_ms: u64,
+    /// After SIGKILL, how long to wait for the preview process group to be reaped
+    pub kill_wait_ms: u64,
+    /// After starting live view, before reporting the preview as up
+    pub preview_stabilize_ms: u64,
+    /// Between SIGTERM and SIGKILL for our own preview process group
+    pub sigterm_grace_ms: u64,
+}
+
+/// Named timing values and the camera models they apply to
+pub struct TimingProfile {
+    pub name: &'static str,
+    /// Matched case-insensitively against the model string from auto-detect
+    pub models: &'static [&'static str],
+    pub timing: CameraTiming,
+}
+
+/// Used when the model matches no other profile; tuned on the EOS Rebel T7
+pub const DEFAULT_TIMING_PROFILE: TimingProfile = TimingProfile {
+    name: "t7",
+    models: &["EOS 2000D", "Rebel T7", "Kiss X90"],
+    timing: CameraTiming {
+        post_preview_stop_ms: 500,
+        kill_wait_ms: 200,
+        preview_stabilize_ms: 1000,
+        sigterm_grace_ms: 100,
+    },
+};
+
+pub const TIMING_PROFILES: &[TimingProfile] = &[
+    DEFAULT_TIMING_PROFILE,
+    // Needs noticeably longer to drop live view and release USB before a capture
+    TimingProfile {
+        name: "250d",
+        models: &["EOS 250D", "Rebel SL3", "Kiss X10", "200D II"],
+        timing: CameraTiming {
+            post_preview_stop_ms: 1500,
+            kill_wait_ms: 500,
+            preview_stabilize_ms: 2000,
+            sigterm_grace_ms: 300,
+        },
+    },
+];
+
+/// How the timing profile is chosen, plus per-value overrides on top of it
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TimingConfig {
+    /// Force a profile by name instead of matching the detected model
+    pub profile: Option<String>,
+    pub post_preview_stop_ms: Option<u64>,
+    pub kill_wait_ms: Option<u64>,
+    pub preview_stabilize_ms: Option<u64>,
+    pub sigterm_grace_ms: Option<u64>,
+}
+
+impl TimingConfig {
+    /// Pick the profile for `model` (or the forced one) and apply the overrides
+    pub fn resolve(&self, model: Option<&str>) -> (&'static str, CameraTiming) {
+        let by_name = self.profile.as_deref().and_then(|name| {
+            TIMING_PROFILES
+                .iter()
+                .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+        });
+        let by_model = || {
+            let model = model?.to_lowercase();
+            TIMING_PROFILES
+                .iter()
+                .find(|p| p.models.iter().any(|m| model.contains(&m.to_lowercase())))
+        };
+        let profile = by_name.or_else(by_model).unwrap_or(&DEFAULT_TIMING_PROFILE);
+
+        let base = profile.timing;
+        let timing = CameraTiming {
+            post_preview_stop_ms: self
+                .post_preview_stop_ms
+                .unwrap_or(base.post_preview_stop_ms),
+            kill_wait_ms: self.kill_wait_ms.unwrap_or(base.kill_wait_ms),
+            preview_stabilize_ms: self
+                .preview_stabilize_ms
+                .unwrap_or(base.preview_stabilize_ms),
+            sigterm_grace_ms: self.sigterm_grace_ms.unwrap_or(base.sigterm_grace_ms),
+        };
+        (profile.name, timing)
+    }
+}
+
+/// Photo-strip burst: several timed captures in one session
+#[derive(Debug, Clone, Deserialize)]
+pub struct BurstConfig {
+    /// Shots per session; 1 disables burst mode
+    pub shots: u32,
+    /// Pause between shots so guests can change pose
+    pub interval_secs: u64,
+}
+
+/// Retry schedule for transient capture failures (camera busy, USB I/O)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    /// Delay before the retry that follows `attempt` (1-based), doubling each time
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CameraBackend {
+    /// Canon DSLR over USB via gphoto2
+    GPhoto,
+    /// Replays JPEGs from a directory, for running without a camera
+    Mock,
+}
+
+impl std::str::FromStr for CameraBackend {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "gphoto" | "gphoto2" => Ok(CameraBackend::GPhoto),
+            "mock" => Ok(CameraBackend::Mock),
+            other => Err(ConfigError::InvalidCameraBackend(other.to_string())),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -40,6 +240,65 @@ pub struct PrinterConfig {
 pub struct TemplateConfig {
     pub story_placeholder: String,
     pub background_filename: String,
+    /// Layout file (TOML or JSON) in the static directory; None uses the built-in poster
+    pub layout_filename: Option<String>,
+    /// What the event prints unless a session picks another format
+    pub print_format: PrintFormat,
+    /// How shots fill their slot unless a session has been re-framed
+    pub photo_fit: PhotoFit,
+    /// Bars around a contained photo
+    pub letterbox_color: [u8; 3],
+    /// Searched once at startup for the fonts layouts name
+    pub font_dirs: Vec<PathBuf>,
+    /// Photo effects for layouts that don't bring their own
+    pub effects: Vec<PhotoEffect>,
+    /// Splits a single-slot layout's photo area between several shots
+    pub collage: Option<Collage>,
+    #[serde(skip)]
+    pub layout_cache: LayoutCache,
+}
+
+/// Server-driven countdown before a capture
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountdownConfig {
+    pub secs: u64,
+    /// How early to stop live view before the shutter; None uses the camera's own estimate
+    pub prepare_lead_ms: Option<u64>,
+}
+
+/// Animated GIF recorded from live view
+#[derive(Debug, Clone, Deserialize)]
+pub struct GifConfig {
+    /// How much live view to record
+    pub duration_ms: u64,
+    pub fps: u32,
+    /// Width of the animated poster; the height follows the print's 2:3
+    pub width: u32,
+    /// Play forward then backward unless the request says otherwise
+    pub boomerang: bool,
+}
+
+/// Frozen-preview detection on the loopback device
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    /// Restart live view after this long without a frame
+    pub stall_secs: u64,
+    /// Wait before the first restart is retried, doubling up to the max while frames stay away
+    pub restart_backoff_secs: u64,
+    pub max_restart_backoff_secs: u64,
+}
+
+impl WatchdogConfig {
+    /// Minimum gap after the `restarts`-th consecutive restart before trying another
+    pub fn backoff(&self, restarts: u32) -> Duration {
+        let factor = 1u64 << restarts.saturating_sub(1).min(16);
+        Duration::from_secs(
+            self.restart_backoff_secs
+                .saturating_mul(factor)
+                .min(self.max_restart_backoff_secs),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -63,13 +322,100 @@ impl Config {
                 .map_err(|_| ConfigError::InvalidPort)?,
         };
 
+        let base_path = std::env::var("STORAGE_PATH")
+            .unwrap_or_else(|_| "/usr/local/share/photo_booth".to_string());
+
         let camera = CameraConfig {
+            backend: std::env::var("CAMERA_BACKEND")
+                .unwrap_or_else(|_| "gphoto".to_string())
+                .parse()?,
             v4l2_loopback_device: std::env::var("V4L2_LOOPBACK_DEVICE")
                 .unwrap_or_else(|_| "/dev/video0".to_string()),
+            mock_image_dir: std::env::var("MOCK_CAMERA_DIR")
+                .unwrap_or_else(|_| format!("{}/mock_camera", base_path))
+                .into(),
+            mock_preview_fps: std::env::var("MOCK_CAMERA_FPS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .unwrap_or(10),
+            reconnect_interval_secs: std::env::var("CAMERA_RECONNECT_INTERVAL_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            capture_retry: RetryPolicy {
+                max_attempts: std::env::var("CAPTURE_RETRY_ATTEMPTS")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse::<u32>()
+                    .unwrap_or(3)
+                    .max(1),
+                initial_backoff_ms: std::env::var("CAPTURE_RETRY_BACKOFF_MS")
+                    .unwrap_or_else(|_| "500".to_string())
+                    .parse()
+                    .unwrap_or(500),
+                max_backoff_ms: std::env::var("CAPTURE_RETRY_MAX_BACKOFF_MS")
+                    .unwrap_or_else(|_| "4000".to_string())
+                    .parse()
+                    .unwrap_or(4000),
+            },
+            burst: BurstConfig {
+                shots: std::env::var("BURST_SHOTS")
+                    .unwrap_or_else(|_| "1".to_string())
+                    .parse::<u32>()
+                    .unwrap_or(1)
+                    .clamp(1, MAX_BURST_SHOTS),
+                interval_secs: std::env::var("BURST_INTERVAL_SECS")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse()
+                    .unwrap_or(3),
+            },
+            timing: TimingConfig {
+                profile: std::env::var("CAMERA_TIMING_PROFILE").ok(),
+                post_preview_stop_ms: std::env::var("CAMERA_POST_PREVIEW_STOP_MS")
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+                kill_wait_ms: std::env::var("CAMERA_KILL_WAIT_MS")
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+                preview_stabilize_ms: std::env::var("CAMERA_PREVIEW_STABILIZE_MS")
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+                sigterm_grace_ms: std::env::var("CAMERA_SIGTERM_GRACE_MS")
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+            },
+            orientation: OrientationConfig {
+                rotation: std::env::var("CAMERA_ROTATION")
+                    .ok()
+                    .and_then(|v| v.parse::<u16>().ok())
+                    .map(|r| r % 360)
+                    .filter(|r| r % 90 == 0)
+                    .unwrap_or(0),
+                mirror_preview: std::env::var("PREVIEW_MIRROR")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+                mirror_capture: std::env::var("CAPTURE_MIRROR")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+            },
+            preview_stream: PreviewStreamConfig {
+                fps: std::env::var("PREVIEW_FPS")
+                    .ok()
+                    .and_then(|v| v.parse::<u32>().ok())
+                    .unwrap_or(MAX_PREVIEW_FPS)
+                    .clamp(1, MAX_PREVIEW_FPS),
+                width: std::env::var("PREVIEW_WIDTH")
+                    .ok()
+                    .and_then(|v| v.parse::<u32>().ok())
+                    .filter(|w| *w > 0),
+                quality: std::env::var("PREVIEW_QUALITY")
+                    .ok()
+                    .and_then(|v| v.parse::<u8>().ok())
+                    .map(|q| q.clamp(1, 100)),
+            },
         };
 
-        let base_path = std::env::var("STORAGE_PATH")
-            .unwrap_or_else(|_| "/usr/local/share/photo_booth".to_string());
         let storage = StorageConfig {
             base_path: PathBuf::from(&base_path),
             static_path: PathBuf::from(&base_path).join("static"),
@@ -89,11 +435,53 @@ impl Config {
                 .unwrap_or(false),
         };
 
+        let letterbox_color = std::env::var("TEMPLATE_LETTERBOX_COLOR")
+            .ok()
+            .and_then(|v| parse_hex_color(&v))
+            .unwrap_or([255, 255, 255]);
         let template = TemplateConfig {
             story_placeholder: std::env::var("TEMPLATE_STORY")
                 .unwrap_or_else(|_| "STORY HERE".to_string()),
             background_filename: std::env::var("TEMPLATE_BACKGROUND")
                 .unwrap_or_else(|_| "combined_background.png".to_string()),
+            layout_filename: std::env::var("TEMPLATE_LAYOUT")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            print_format: std::env::var("PRINT_FORMAT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            photo_fit: match std::env::var("TEMPLATE_PHOTO_FIT")
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+                .as_str()
+            {
+                "contain" => PhotoFit::Contain {
+                    letterbox: letterbox_color,
+                },
+                _ => PhotoFit::Cover,
+            },
+            letterbox_color,
+            font_dirs: match std::env::var("FONT_DIRS") {
+                Ok(dirs) if !dirs.trim().is_empty() => std::env::split_paths(&dirs).collect(),
+                _ => DEFAULT_FONT_DIRS.iter().map(PathBuf::from).collect(),
+            },
+            effects: {
+                let name = std::env::var("TEMPLATE_EFFECTS").unwrap_or_default();
+                photo_effects::preset(&name).unwrap_or_else(|| {
+                    warn!("Unknown TEMPLATE_EFFECTS preset '{}', using none", name);
+                    Vec::new()
+                })
+            },
+            collage: match std::env::var("TEMPLATE_COLLAGE") {
+                Ok(v) if !v.trim().is_empty() && v.trim() != "none" => v
+                    .parse()
+                    .map_err(|e| warn!("{}, printing one photo", e))
+                    .ok(),
+                _ => None,
+            },
+            layout_cache: LayoutCache::default(),
         };
 
         let database = DatabaseConfig {
@@ -102,6 +490,56 @@ impl Config {
                 .into(),
         };
 
+        let countdown = CountdownConfig {
+            secs: std::env::var("COUNTDOWN_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            prepare_lead_ms: std::env::var("CAPTURE_PREPARE_LEAD_MS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+        };
+
+        let watchdog = WatchdogConfig {
+            enabled: std::env::var("PREVIEW_WATCHDOG")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            stall_secs: std::env::var("PREVIEW_STALL_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse::<u64>()
+                .unwrap_or(10)
+                .max(1),
+            restart_backoff_secs: std::env::var("PREVIEW_RESTART_BACKOFF_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            max_restart_backoff_secs: std::env::var("PREVIEW_RESTART_MAX_BACKOFF_SECS")
+                .unwrap_or_else(|_| "120".to_string())
+                .parse()
+                .unwrap_or(120),
+        };
+
+        let gif = GifConfig {
+            duration_ms: std::env::var("GIF_DURATION_MS")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .unwrap_or(2000),
+            fps: std::env::var("GIF_FPS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse::<u32>()
+                .unwrap_or(10)
+                .clamp(1, 25),
+            width: std::env::var("GIF_WIDTH")
+                .unwrap_or_else(|_| "480".to_string())
+                .parse()
+                .unwrap_or(480),
+            boomerang: std::env::var("GIF_BOOMERANG")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+        };
+
         let config = Config {
             server,
             camera,
@@ -109,6 +547,9 @@ impl Config {
             printer,
             template,
             database,
+            countdown,
+            watchdog,
+            gif,
         };
 
         config.validate()?;
@@ -139,17 +580,110 @@ impl Config {
             .static_path
             .join(&self.template.background_filename)
     }
+
+    /// The print layout for `format`: the event's layout file when it is drawn for a print
+    /// of that size, otherwise the built-in one. The file is parsed again only after it
+    /// changes, so a designer's edits show up on the next print; a layout that fails to
+    /// load falls back as well
+    pub fn template_layout(&self, format: PrintFormat) -> TemplateLayout {
+        let layout = self.template.layout_filename.as_ref().and_then(|name| {
+            let path = self.storage.static_path.join(name);
+            let layout = self
+                .template
+                .layout_cache
+                .load(&path)
+                .map_err(|e| warn!("{}, using the built-in layout", e))
+                .ok()?;
+            if layout.format.copy_size() != format.copy_size() {
+                warn!(
+                    "Layout {} is drawn for {} prints, not {}; using the built-in layout",
+                    path.display(),
+                    layout.format,
+                    format
+                );
+                return None;
+            }
+            Some(TemplateLayout { format, ..layout })
+        });
+        let mut layout = layout
+            .unwrap_or_else(|| TemplateLayout::for_format(format))
+            .with_default_background(self.background_path());
+        if layout.effects.is_empty() && layout.class_effects.is_empty() {
+            layout.effects = self.template.effects.clone();
+        }
+        match self.template.collage {
+            Some(collage) => layout.with_collage(collage),
+            None => layout,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("Invalid port number")]
     InvalidPort,
+
+    #[error("Unknown camera backend: {0}")]
+    InvalidCameraBackend(String),
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::camera::tests::mock_config;
+    use std::path::Path;
+
+    /// Config rooted at `base_path` with a mock camera replaying `base_path/mock`, for
+    /// tests that must not read the process environment
+    pub(crate) fn test_config(base_path: &Path) -> Config {
+        Config {
+            server: ServerConfig {
+                host: "127.0.0.1".to_string(),
+                port: 8080,
+            },
+            camera: mock_config(&base_path.join("mock")),
+            storage: StorageConfig {
+                base_path: base_path.to_path_buf(),
+                static_path: base_path.join("static"),
+            },
+            printer: PrinterConfig {
+                name: "mock".to_string(),
+                fallback_names: Vec::new(),
+                use_mock: true,
+            },
+            template: TemplateConfig {
+                story_placeholder: "STORY HERE".to_string(),
+                background_filename: "combined_background.png".to_string(),
+                layout_filename: None,
+                print_format: PrintFormat::default(),
+                photo_fit: PhotoFit::Cover,
+                letterbox_color: [255, 255, 255],
+                font_dirs: Vec::new(),
+                effects: Vec::new(),
+                collage: None,
+                layout_cache: LayoutCache::default(),
+            },
+            database: DatabaseConfig {
+                path: base_path.join("photo_booth.db"),
+            },
+            countdown: CountdownConfig {
+                secs: 5,
+                prepare_lead_ms: None,
+            },
+            watchdog: WatchdogConfig {
+                enabled: false,
+                stall_secs: 10,
+                restart_backoff_secs: 5,
+                max_restart_backoff_secs: 120,
+            },
+            gif: GifConfig {
+                duration_ms: 2000,
+                fps: 10,
+                width: 480,
+                boomerang: false,
+            },
+        }
+    }
 
     #[test]
     fn test_default_config() {
@@ -159,6 +693,54 @@ mod tests {
         let config = Config::from_env().expect("Failed to create config");
         assert_eq!(config.server.port, 8080);
         assert_eq!(config.camera.v4l2_loopback_device, "/dev/video0");
+        assert_eq!(config.camera.backend, CameraBackend::GPhoto);
+    }
+
+    #[test]
+    fn test_camera_backend_parse() {
+        assert_eq!(
+            "mock".parse::<CameraBackend>().unwrap(),
+            CameraBackend::Mock
+        );
+        assert_eq!(
+            "GPhoto2".parse::<CameraBackend>().unwrap(),
+            CameraBackend::GPhoto
+        );
+        assert!(matches!(
+            "webcam".parse::<CameraBackend>(),
+            Err(ConfigError::InvalidCameraBackend(_))
+        ));
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles_and_caps() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 1500,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(3), Duration::from_millis(1500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_timing_profile_from_model_with_overrides() {
+        let config = TimingConfig::default();
+        assert_eq!(config.resolve(Some("Canon EOS 250D")).0, "250d");
+        assert_eq!(config.resolve(Some("Canon EOS 2000D")).0, "t7");
+        assert_eq!(config.resolve(None), ("t7", DEFAULT_TIMING_PROFILE.timing));
+
+        let config = TimingConfig {
+            profile: Some("250D".to_string()),
+            sigterm_grace_ms: Some(50),
+            ..TimingConfig::default()
+        };
+        let (name, timing) = config.resolve(Some("Canon EOS 2000D"));
+        assert_eq!(name, "250d");
+        assert_eq!(timing.sigterm_grace_ms, 50);
+        assert_eq!(timing.post_preview_stop_ms, 1500);
     }
 
     #[test]
@@ -48,10 +48,37 @@ pub enum CameraError {
     #[error("No frame available")]
     NoFrameAvailable,
 
+    #[error("Camera is busy: {0}")]
+    Busy(String),
+
     #[error("Camera I/O error: {0}")]
     IoError(#[from] io::Error),
 }
 
+impl CameraError {
+    /// Short machine-readable name reported to the kiosk
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CameraError::DeviceNotFound { .. } => "device_not_found",
+            CameraError::OpenFailed(_) => "open_failed",
+            CameraError::FormatError(_) => "format_error",
+            CameraError::StreamStartError(_) => "stream_start_error",
+            CameraError::CaptureError(_) => "capture_error",
+            CameraError::NoFrameAvailable => "no_frame_available",
+            CameraError::Busy(_) => "busy",
+            CameraError::IoError(_) => "io_error",
+        }
+    }
+
+    /// Whether trying again shortly is likely to succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            CameraError::Busy(_) | CameraError::IoError(_) | CameraError::NoFrameAvailable
+        )
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum PrinterError {
     #[error("Printer not found: {name}")]
@@ -206,6 +233,19 @@ mod tests {
         assert_eq!(err.to_string(), "Camera device not found: /dev/video0");
     }
 
+    #[test]
+    fn test_camera_error_kind() {
+        let err = CameraError::Busy("I/O in progress".to_string());
+        assert_eq!(err.kind(), "busy");
+        assert!(err.is_transient());
+
+        let err = CameraError::DeviceNotFound {
+            device: "usb".to_string(),
+        };
+        assert_eq!(err.kind(), "device_not_found");
+        assert!(!err.is_transient());
+    }
+
     #[test]
     fn test_app_error_status_codes() {
         let err = AppError::Storage(StorageError::FileNotFound {
@@ -0,0 +1,293 @@
+// Font registry: indexes the configured font directories once, loads each font on first
+// use and keeps it, and falls back to a font built into the binary when one is missing
+
+use rusttype::Font;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{debug, info, warn};
+
+use crate::templates::TemplateError;
+
+/// DejaVu Serif, so text still prints when no configured font can be found
+static FALLBACK_FONT: &[u8] = include_bytes!("../fonts/DejaVuSerif.ttf");
+
+// Searched when FONT_DIRS isn't set
+pub const DEFAULT_FONT_DIRS: [&str; 2] = ["/usr/local/share/fonts", "/usr/share/fonts"];
+// Font directories are usually nested a level or two by vendor
+const MAX_SCAN_DEPTH: usize = 4;
+
+// Registry for templates not handed the app's, built on first use
+static BUILT_IN_ONLY: OnceLock<Arc<FontRegistry>> = OnceLock::new();
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum FontStyle {
+    #[default]
+    Regular,
+    Italic,
+    Bold,
+    BoldItalic,
+}
+
+impl FontStyle {
+    /// Style named by a font file's suffix, e.g. the `Italic` in `IMFellEnglish-Italic.ttf`
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix.to_lowercase().as_str() {
+            "regular" | "roman" | "book" => Some(FontStyle::Regular),
+            "italic" | "oblique" => Some(FontStyle::Italic),
+            "bold" => Some(FontStyle::Bold),
+            "bolditalic" | "boldoblique" => Some(FontStyle::BoldItalic),
+            _ => None,
+        }
+    }
+}
+
+/// Font status as reported by /health and /metrics
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FontHealth {
+    /// Family and style of every font found in the font directories
+    pub available: Vec<String>,
+    /// Fonts asked for but not found; their text was drawn in the fallback font
+    pub missing: Vec<String>,
+}
+
+pub struct FontRegistry {
+    /// Font files found in the font directories, by normalised family name and style
+    files: HashMap<(String, FontStyle), PathBuf>,
+    loaded: Mutex<HashMap<PathBuf, Arc<Font<'static>>>>,
+    missing: Mutex<BTreeSet<String>>,
+    fallback: Arc<Font<'static>>,
+}
+
+impl FontRegistry {
+    pub fn new(dirs: &[PathBuf]) -> Self {
+        let mut files = HashMap::new();
+        for dir in dirs {
+            scan_dir(dir, 0, &mut files);
+        }
+        info!(
+            "Found {} font(s) in {}",
+            files.len(),
+            dirs.iter()
+                .map(|d| d.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        FontRegistry {
+            files,
+            loaded: Mutex::new(HashMap::new()),
+            missing: Mutex::new(BTreeSet::new()),
+            fallback: Arc::new(
+                Font::try_from_bytes(FALLBACK_FONT).expect("embedded fallback font is valid"),
+            ),
+        }
+    }
+
+    /// Look up a font by family and style. `name` may also be the path of a font file,
+    /// as older layouts name them
+    pub fn font(&self, name: &str, style: FontStyle) -> Result<Arc<Font<'static>>, TemplateError> {
+        let path = if is_font_path(name) {
+            PathBuf::from(name)
+        } else {
+            self.files
+                .get(&(family_key(name), style))
+                .cloned()
+                .ok_or_else(|| {
+                    TemplateError::FontError(format!(
+                        "no {} font for '{}'",
+                        style_name(style),
+                        name
+                    ))
+                })?
+        };
+        self.load(&path)
+    }
+
+    /// Like `font`, but never fails: a missing style falls back to the family's regular
+    /// face, and a missing family to the built-in font. Misses are logged once and
+    /// reported in the health status
+    pub fn font_or_fallback(&self, name: &str, style: FontStyle) -> Arc<Font<'static>> {
+        let err = match self.font(name, style) {
+            Ok(font) => return font,
+            Err(e) => e,
+        };
+        self.record_missing(name, style, &err);
+        if style != FontStyle::Regular && !is_font_path(name) {
+            if let Ok(font) = self.font(name, FontStyle::Regular) {
+                return font;
+            }
+        }
+        self.fallback.clone()
+    }
+
+    pub fn health(&self) -> FontHealth {
+        let mut available: Vec<String> = self
+            .files
+            .iter()
+            .map(|((_, style), path)| {
+                let family = path.file_stem().unwrap_or_default().to_string_lossy();
+                let family = family.split('-').next().unwrap_or_default();
+                format!("{} {}", family, style_name(*style))
+            })
+            .collect();
+        available.sort();
+        FontHealth {
+            available,
+            missing: self.missing.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+
+    fn load(&self, path: &Path) -> Result<Arc<Font<'static>>, TemplateError> {
+        if let Some(font) = self.loaded.lock().unwrap().get(path) {
+            return Ok(font.clone());
+        }
+        let bytes = std::fs::read(path)
+            .map_err(|e| TemplateError::FontError(format!("{}: {}", path.display(), e)))?;
+        let font = Font::try_from_vec(bytes).map(Arc::new).ok_or_else(|| {
+            TemplateError::FontError(format!("{}: not a usable font", path.display()))
+        })?;
+        debug!("Loaded font {}", path.display());
+        self.loaded
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), font.clone());
+        Ok(font)
+    }
+
+    fn record_missing(&self, name: &str, style: FontStyle, err: &TemplateError) {
+        let entry = if is_font_path(name) {
+            name.to_string()
+        } else {
+            format!("{} {}", name, style_name(style))
+        };
+        if self.missing.lock().unwrap().insert(entry) {
+            warn!("{}, using a fallback font", err);
+        }
+    }
+}
+
+/// A registry with no font directories, so every lookup gets the built-in font. Shared,
+/// so the embedded font is parsed once however many templates use it
+pub fn built_in_only() -> Arc<FontRegistry> {
+    BUILT_IN_ONLY
+        .get_or_init(|| Arc::new(FontRegistry::new(&[])))
+        .clone()
+}
+
+/// Whether a layout's font names a file rather than a family
+pub fn is_font_path(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    name.contains('/') || lower.ends_with(".ttf") || lower.ends_with(".otf")
+}
+
+fn style_name(style: FontStyle) -> &'static str {
+    match style {
+        FontStyle::Regular => "regular",
+        FontStyle::Italic => "italic",
+        FontStyle::Bold => "bold",
+        FontStyle::BoldItalic => "bold italic",
+    }
+}
+
+/// "IM Fell English", "imfell_english" and "IMFellEnglish" are the same family
+fn family_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Family and style from a font file name: `Family-Style.ttf`, or `Family.ttf` for the
+/// regular face
+fn parse_font_file_name(path: &Path) -> Option<(String, FontStyle)> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    if extension != "ttf" && extension != "otf" {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    match stem.rsplit_once('-') {
+        // Weights we don't use, e.g. ExtraLight, are left out
+        Some((family, suffix)) => {
+            FontStyle::from_suffix(suffix).map(|style| (family_key(family), style))
+        }
+        None => Some((family_key(stem), FontStyle::Regular)),
+    }
+}
+
+fn scan_dir(dir: &Path, depth: usize, files: &mut HashMap<(String, FontStyle), PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Skipping font directory {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    // Keep the first of duplicate names stable between runs
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if depth < MAX_SCAN_DEPTH {
+                scan_dir(&path, depth + 1, files);
+            }
+        } else if let Some(key) = parse_font_file_name(&path) {
+            files.entry(key).or_insert(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_file_names() {
+        assert_eq!(
+            parse_font_file_name(Path::new("/f/IMFellEnglish-Italic.ttf")),
+            Some(("imfellenglish".to_string(), FontStyle::Italic))
+        );
+        assert_eq!(
+            parse_font_file_name(Path::new("/f/DejaVuSerif.ttf")),
+            Some(("dejavuserif".to_string(), FontStyle::Regular))
+        );
+        assert_eq!(
+            parse_font_file_name(Path::new("/f/Lato-BoldItalic.OTF")),
+            Some(("lato".to_string(), FontStyle::BoldItalic))
+        );
+        assert_eq!(
+            parse_font_file_name(Path::new("/f/Lato-Hairline.ttf")),
+            None
+        );
+        assert_eq!(parse_font_file_name(Path::new("/f/README.txt")), None);
+    }
+
+    #[test]
+    fn test_lookup_falls_back_and_reports_missing_fonts() {
+        let dir = std::env::temp_dir().join(format!("fonts_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/Booth Serif-Italic.ttf"), FALLBACK_FONT).unwrap();
+
+        let registry = FontRegistry::new(std::slice::from_ref(&dir));
+        assert!(registry.font("booth_serif", FontStyle::Italic).is_ok());
+        assert!(matches!(
+            registry.font("Booth Serif", FontStyle::Bold),
+            Err(TemplateError::FontError(_))
+        ));
+        assert!(registry.health().missing.is_empty());
+
+        // Missing lookups still hand back something to draw with
+        registry.font_or_fallback("Booth Serif", FontStyle::Bold);
+        registry.font_or_fallback("Nowhere", FontStyle::Regular);
+        registry.font_or_fallback("Nowhere", FontStyle::Regular);
+        let health = registry.health();
+        assert_eq!(health.available, vec!["Booth Serif italic"]);
+        assert_eq!(health.missing, vec!["Booth Serif bold", "Nowhere regular"]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
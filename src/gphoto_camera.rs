@@ -1,230 +1,408 @@
 // GPhoto2-based camera implementation for Canon EOS Rebel T7
-// Uses gphoto2 CLI for preview streaming and capture operations
-
-use std::os::unix::process::CommandExt;
-use std::process::{Child, Command, Stdio};
+// Holds one in-process libgphoto2 session for detection, config, live view and capture.
+// Live view frames are piped through ffmpeg into the v4l2loopback device.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use gphoto2::error::ErrorKind;
+use gphoto2::widget::Widget;
+use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
+use crate::camera::{Camera, CameraStatus, PreviewFrames};
+use crate::camera_state::{CameraState, CameraStateMachine};
 // Use the camera config from the config module
-use crate::config::CameraConfig;
+use crate::config::{CameraConfig, CameraTiming, PreviewStreamConfig};
+use crate::errors::CameraError;
+use crate::process_supervisor::{ManagedChild, ProcessSupervisor};
+
+// Rough cost of ending the live view loop and closing the viewfinder, on top of the
+// profile's waits
+const PREVIEW_STOP_OVERHEAD_MS: u64 = 300;
 
 pub struct GPhotoCamera {
     config: CameraConfig,
-    preview_process: Arc<Mutex<Option<Child>>>,
-    is_streaming: Arc<Mutex<bool>>,
+    context: gphoto2::Context,
+    // The open USB session; the async mutex serializes live view and capture
+    session: Arc<tokio::sync::Mutex<Option<gphoto2::Camera>>>,
+    model: Mutex<Option<String>>,
+    // Delays for the connected model; the config's default profile until one is detected
+    timing: Mutex<CameraTiming>,
+    // Owns every ffmpeg this camera starts; nothing else is ever signalled
+    processes: Arc<ProcessSupervisor>,
+    preview_process: Mutex<Option<ManagedChild>>,
+    preview_task: Mutex<Option<JoinHandle<()>>>,
+    state: Arc<CameraStateMachine>,
 }
 
 impl GPhotoCamera {
     /// Create a new GPhotoCamera instance
-    pub fn new(config: CameraConfig) -> Result<Self, String> {
+    pub fn new(config: CameraConfig, state: Arc<CameraStateMachine>) -> Result<Self, String> {
+        let context = gphoto2::Context::new()
+            .map_err(|e| format!("Failed to create gphoto2 context: {}", e))?;
+
+        let (_, timing) = config.timing.resolve(None);
+
         Ok(GPhotoCamera {
             config,
-            preview_process: Arc::new(Mutex::new(None)),
-            is_streaming: Arc::new(Mutex::new(false)),
+            context,
+            session: Arc::new(tokio::sync::Mutex::new(None)),
+            model: Mutex::new(None),
+            timing: Mutex::new(timing),
+            processes: Arc::new(ProcessSupervisor::new()),
+            preview_process: Mutex::new(None),
+            preview_task: Mutex::new(None),
+            state,
         })
     }
 
-    /// Kill any existing gphoto2 and related processes
-    fn kill_gphoto_processes() {
-        debug!("Killing gphoto2 processes with SIGTERM...");
-        // Kill gphoto2 processes
-        let _ = Command::new("pkill").args(&["-f", "gphoto2"]).output();
-        // Kill any ffmpeg processes that might be connected to v4l2 devices
-        let _ = Command::new("pkill").args(&["-f", "ffmpeg.*v4l2"]).output();
-        // Give processes time to die
-        debug!("Waiting 200ms for graceful termination...");
-        std::thread::sleep(Duration::from_millis(200));
-        // Force kill if still running
-        debug!("Force killing any remaining processes with SIGKILL...");
-        let _ = Command::new("pkill")
-            .args(&["-9", "-f", "gphoto2"])
-            .output();
-        let _ = Command::new("pkill")
-            .args(&["-9", "-f", "ffmpeg.*v4l2"])
-            .output();
-
-        // Verify processes are dead
-        let check = Command::new("pgrep").args(&["-f", "gphoto2"]).output();
-        if let Ok(output) = check {
-            if !output.stdout.is_empty() {
-                warn!("Some gphoto2 processes still running after kill attempt!");
-            } else {
-                debug!("All gphoto2 processes successfully terminated");
-            }
+    /// Point out gphoto2 CLI processes (e.g. from the troubleshooting scripts) that may
+    /// keep the USB device claimed. They aren't ours, so they are left alone
+    fn warn_about_stray_gphoto_processes() {
+        let Ok(output) = Command::new("pgrep").args(["-a", "gphoto2"]).output() else {
+            return;
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            warn!(
+                "gphoto2 process not started by the booth may hold the camera: {}",
+                line
+            );
         }
     }
 
-    /// Initialize and connect to the camera
-    pub async fn initialize(&self) -> Result<(), String> {
+    fn timing(&self) -> CameraTiming {
+        *self.timing.lock().unwrap()
+    }
+
+    /// Detect the camera on USB and open the gphoto2 session
+    async fn connect(&self) -> Result<(), String> {
         info!("Initializing Canon EOS camera via USB...");
 
-        // Kill any existing gphoto2 processes
-        Self::kill_gphoto_processes();
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        Self::warn_about_stray_gphoto_processes();
 
-        // Check if camera is connected using gphoto2 --auto-detect
-        let output = tokio::process::Command::new("gphoto2")
-            .arg("--auto-detect")
-            .output()
+        let cameras: Vec<_> = self
+            .context
+            .list_cameras()
             .await
-            .map_err(|e| format!("Failed to run gphoto2 --auto-detect: {}", e))?;
+            .map_err(|e| format!("Failed to list cameras: {}", e))?
+            .collect();
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        info!("Camera detection output: {}", output_str);
+        info!("Camera detection found {} camera(s)", cameras.len());
+        for descriptor in &cameras {
+            info!("  - {} on {}", descriptor.model, descriptor.port);
+        }
 
-        // Check if a camera was detected (look for lines with USB)
-        if !output_str.contains("usb:") {
-            return Err(
-                "No camera detected. Please ensure camera is connected and turned on.".to_string(),
+        // Only consider cameras on USB
+        let descriptor = cameras
+            .iter()
+            .find(|d| d.port.starts_with("usb:"))
+            .ok_or_else(|| {
+                "No camera detected. Please ensure camera is connected and turned on.".to_string()
+            })?;
+
+        let camera = self
+            .context
+            .get_camera(descriptor)
+            .await
+            .map_err(|e| format!("Failed to open camera {}: {}", descriptor.model, e))?;
+
+        // Models differ in how long they need to switch modes
+        let (profile, timing) = self.config.timing.resolve(Some(&descriptor.model));
+        info!(
+            "Using '{}' timing profile for {}: {:?}",
+            profile, descriptor.model, timing
+        );
+        *self.timing.lock().unwrap() = timing;
+        *self.model.lock().unwrap() = Some(descriptor.model.clone());
+        *self.session.lock().await = Some(camera);
+
+        // Log the exposure settings we start the event with
+        for key in ["iso", "aperture", "shutterspeed", "imageformat"] {
+            match self.get_config_value(key).await {
+                Ok(value) => info!("  {} = {}", key, value),
+                Err(e) => debug!("  {} unavailable: {}", key, e),
+            }
+        }
+
+        info!("Camera initialized successfully: {}", descriptor.model);
+        Ok(())
+    }
+
+    /// Trigger the shutter, download the shot and write it to `output_path`
+    async fn capture_on_session(
+        &self,
+        output_path: &str,
+        capture_start: std::time::Instant,
+    ) -> Result<Vec<u8>, CameraError> {
+        let guard = self.session.lock().await;
+        let camera = guard.as_ref().ok_or_else(|| CameraError::DeviceNotFound {
+            device: "usb".to_string(),
+        })?;
+
+        info!("Triggering capture on the gphoto2 session...");
+        let capture_cmd_start = std::time::Instant::now();
+
+        let file_path = camera.capture_image().await.map_err(|e| {
+            error!(
+                "Capture failed after {:?}: {} ({:?})",
+                capture_cmd_start.elapsed(),
+                e,
+                e.kind()
             );
+            error!("Total time before failure: {:?}", capture_start.elapsed());
+
+            // Check for specific error patterns
+            match e.kind() {
+                ErrorKind::CameraBusy => {
+                    error!("Camera is busy - may need longer delay after stopping preview")
+                }
+                ErrorKind::IoLock | ErrorKind::IoUsbClaim => {
+                    error!("USB device is locked - another process may be using the camera")
+                }
+                _ => {}
+            }
+
+            camera_error(e)
+        })?;
+
+        let folder = file_path.folder().to_string();
+        let name = file_path.name().to_string();
+        info!(
+            "Capture command completed in: {:?} ({}/{})",
+            capture_cmd_start.elapsed(),
+            folder,
+            name
+        );
+
+        let camera_file = camera
+            .fs()
+            .download(&folder, &name)
+            .await
+            .map_err(camera_error)?;
+        let jpeg_data = camera_file
+            .get_data(&self.context)
+            .await
+            .map_err(camera_error)?
+            .into_vec();
+
+        // Don't let shots pile up on the camera
+        if let Err(e) = camera.fs().delete_file(&folder, &name).await {
+            debug!("Could not delete {}/{} from camera: {}", folder, name, e);
+        }
+        drop(guard);
+
+        tokio::fs::write(output_path, &jpeg_data).await?;
+
+        info!("=== CAPTURE PHOTO SUCCESS ===");
+        info!("Photo captured successfully: {}", output_path);
+        info!("Total capture time: {:?}", capture_start.elapsed());
+
+        Ok(jpeg_data)
+    }
+
+    /// Bring the camera back to Idle after a failed operation so the next one can start
+    fn resume_from_error(&self) -> Result<(), String> {
+        if self.state.current() == CameraState::Error {
+            self.state
+                .transition(CameraState::Idle, "retrying after error")?;
         }
+        Ok(())
+    }
+
+    /// Internal method to stop preview without async
+    async fn stop_preview_internal(&self) {
+        let stop_start = std::time::Instant::now();
+
+        // Let the live view loop finish its current frame and release the session
+        let task = self.preview_task.lock().unwrap().take();
+        if let Some(mut task) = task {
+            debug!("Waiting for live view loop to exit...");
+            if tokio::time::timeout(Duration::from_secs(2), &mut task)
+                .await
+                .is_err()
+            {
+                warn!("Live view loop did not exit in time, aborting it");
+                task.abort();
+            }
+        }
+
+        // Stop our ffmpeg and everything in its process group
+        let process = self.preview_process.lock().unwrap().take();
+        if let Some(process) = process {
+            info!("Stopping preview process PID {}", process.pid());
+            let timing = self.timing();
+            process
+                .stop(
+                    Duration::from_millis(timing.sigterm_grace_ms),
+                    Duration::from_millis(timing.kill_wait_ms),
+                )
+                .await;
+            info!("Preview process stopped in {:?}", stop_start.elapsed());
+        } else {
+            debug!("No preview process to stop");
+        }
+
+        info!("Preview stop completed in {:?}", stop_start.elapsed());
+    }
+}
 
-        info!("Camera initialized successfully");
+#[async_trait]
+impl Camera for GPhotoCamera {
+    /// Initialize and connect to the camera
+    async fn initialize(&self) -> Result<(), String> {
+        self.connect().await.inspect_err(|e| self.state.fail(e))?;
+        let _ = self.state.transition(CameraState::Idle, "camera connected");
         Ok(())
     }
 
-    /// Start the camera preview stream using gphoto2 CLI and v4l2loopback
-    pub async fn start_preview_stream(&self) -> Result<(), String> {
-        // Check if already streaming
-        {
-            let is_streaming = self.is_streaming.lock().unwrap();
-            if *is_streaming {
-                warn!("Preview stream already running");
-                return Ok(());
+    /// Check USB for a camera; safe to call while the session is open
+    async fn is_present(&self) -> bool {
+        match self.context.list_cameras().await {
+            Ok(mut cameras) => cameras.any(|d| d.port.starts_with("usb:")),
+            Err(e) => {
+                debug!("Camera detection failed: {}", e);
+                false
             }
         }
+    }
+
+    /// Start live view, feeding frames through ffmpeg into the v4l2loopback device
+    async fn start_preview(&self) -> Result<(), String> {
+        // Check if already streaming
+        if self.state.current() == CameraState::Previewing {
+            warn!("Preview stream already running");
+            return Ok(());
+        }
+        self.resume_from_error()?;
 
         info!("Starting camera preview stream...");
 
         // Stop any existing preview
         self.stop_preview_internal().await;
 
-        // Kill any stray gphoto2 processes
-        Self::kill_gphoto_processes();
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        if self.session.lock().await.is_none() {
+            return Err("Camera not connected".to_string());
+        }
 
-        // Start gphoto2 preview stream to v4l2loopback device
         let v4l2_device = self.config.v4l2_loopback_device.clone(); // e.g., "/dev/video0"
 
-        info!("Starting gphoto2 preview stream to {}", v4l2_device);
+        info!("Starting live view feed to {}", v4l2_device);
 
-        // Use bash to run the piped command
-        // Set process group to ensure all children are killed together
-        let mut cmd = Command::new("bash");
+        // ffmpeg decodes the live view JPEGs and writes raw frames to the loopback device
+        let mut cmd = tokio::process::Command::new("ffmpeg");
         cmd.args(&[
-            "-c",
-            &format!(
-                "gphoto2 --stdout --capture-movie | ffmpeg -i - -vcodec rawvideo -pix_fmt yuv420p -threads 0 -f v4l2 {}",
-                v4l2_device
-            )
+            // Only problems on stderr, not a progress line per frame
+            "-nostats",
+            "-loglevel",
+            "warning",
+            "-f",
+            "mjpeg",
+            "-i",
+            "-",
+            "-vcodec",
+            "rawvideo",
+            "-pix_fmt",
+            "yuv420p",
+            "-threads",
+            "0",
+            "-f",
+            "v4l2",
+            &v4l2_device,
         ])
-        .stdin(Stdio::null())
+        .stdin(Stdio::piped())
         .stdout(Stdio::null())
-        .stderr(Stdio::null());
-
-        // Create a new process group so we can kill all children
-        unsafe {
-            cmd.pre_exec(|| {
-                libc::setpgid(0, 0);
-                Ok(())
-            });
-        }
+        .stderr(Stdio::piped());
+
+        let mut preview_cmd = self.processes.spawn("ffmpeg (live view)", cmd)?;
 
-        let preview_cmd = cmd
-            .spawn()
-            .map_err(|e| format!("Failed to start preview command: {}", e))?;
+        let stdin = preview_cmd
+            .stdin
+            .take()
+            .ok_or_else(|| "Failed to open ffmpeg stdin".to_string())?;
+        if let Some(stderr) = preview_cmd.stderr.take() {
+            log_child_stderr("ffmpeg (live view)", stderr);
+        }
 
         // Store the process handle
         *self.preview_process.lock().unwrap() = Some(preview_cmd);
 
-        // Set streaming flag
-        *self.is_streaming.lock().unwrap() = true;
+        if let Err(e) = self
+            .state
+            .transition(CameraState::Previewing, "live view started")
+        {
+            self.stop_preview_internal().await;
+            return Err(e);
+        }
+
+        let task = tokio::spawn(run_live_view(
+            self.context.clone(),
+            self.session.clone(),
+            self.state.clone(),
+            stdin,
+        ));
+        *self.preview_task.lock().unwrap() = Some(task);
 
         // Give the stream a moment to stabilize
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        tokio::time::sleep(Duration::from_millis(self.timing().preview_stabilize_ms)).await;
 
         info!("Preview stream started successfully");
         Ok(())
     }
 
-    /// Internal method to stop preview without async
-    async fn stop_preview_internal(&self) {
-        let stop_start = std::time::Instant::now();
-
-        // Kill the preview process if it exists
-        if let Some(mut process) = self.preview_process.lock().unwrap().take() {
-            let pid = process.id();
-            info!("Killing preview process PID {} and its children", pid);
-
-            // Try to get the process ID
-            // Kill the entire process group (negative PID kills the group)
-            debug!("Sending SIGTERM to process group -{}", pid);
-            unsafe {
-                libc::kill(-(pid as i32), libc::SIGTERM);
-            }
-            // Give it a moment to terminate gracefully
-            debug!("Waiting 100ms for graceful termination...");
-            std::thread::sleep(Duration::from_millis(100));
-            // Force kill if still running
-            debug!("Sending SIGKILL to process group -{}", pid);
-            unsafe {
-                libc::kill(-(pid as i32), libc::SIGKILL);
-            }
-
-            // Also try the standard kill
-            let _ = process.kill();
-            let wait_result = process.wait();
-            debug!("Process wait result: {:?}", wait_result);
-
-            info!("Preview process killed in {:?}", stop_start.elapsed());
-        } else {
-            debug!("No preview process to kill");
+    /// Stop the camera preview stream
+    async fn stop_preview(&self) -> Result<(), String> {
+        if self.state.current() != CameraState::Previewing {
+            debug!("Preview not running, nothing to stop");
+            return Ok(());
         }
 
-        // Kill any remaining gphoto2/ffmpeg processes
-        debug!("Cleaning up any remaining processes...");
-        Self::kill_gphoto_processes();
-        info!("Preview stop completed in {:?}", stop_start.elapsed());
-    }
-
-    /// Stop the camera preview stream
-    pub async fn stop_preview(&self) -> Result<(), String> {
         info!("Stopping camera preview...");
 
-        // Set streaming flag to false
-        *self.is_streaming.lock().unwrap() = false;
+        // Leaving Previewing also tells the live view loop to exit
+        self.state
+            .transition(CameraState::StoppingPreview, "preview stop requested")?;
 
         // Stop the preview process
         self.stop_preview_internal().await;
 
+        // Drop the mirror so the next capture doesn't fight live view
+        if let Err(e) = self.set_config_value("viewfinder", "0").await {
+            debug!("Could not close viewfinder: {}", e);
+        }
+
+        self.state
+            .transition(CameraState::Idle, "preview stopped")?;
         info!("Preview stopped");
         Ok(())
     }
 
-    /// Capture a high-resolution photo using gphoto2 CLI
-    pub async fn capture_photo(&self, output_path: &str) -> Result<Vec<u8>, String> {
+    /// Capture a high-resolution photo on the open gphoto2 session
+    async fn capture_photo(&self, output_path: &str) -> Result<Vec<u8>, CameraError> {
         let capture_start = std::time::Instant::now();
         info!("=== CAPTURE PHOTO START ===");
         info!("Output path: {}", output_path);
         info!("Capture started at: {:?}", capture_start);
 
         // Stop preview if running
-        let is_streaming = *self.is_streaming.lock().unwrap();
+        let is_streaming = self.state.current() == CameraState::Previewing;
         info!("Preview streaming status: {}", is_streaming);
 
         if is_streaming {
             info!("Stopping preview before capture...");
             let stop_start = std::time::Instant::now();
-            self.stop_preview().await?;
+            self.stop_preview().await.map_err(CameraError::Busy)?;
             let stop_duration = stop_start.elapsed();
             info!("Preview stopped in: {:?}", stop_duration);
 
             // Wait a bit for camera to be ready
-            info!("Waiting 500ms for camera state transition...");
-            tokio::time::sleep(Duration::from_millis(500)).await;
+            let settle = Duration::from_millis(self.timing().post_preview_stop_ms);
+            info!("Waiting {:?} for camera state transition...", settle);
+            tokio::time::sleep(settle).await;
             info!(
                 "Wait complete, elapsed since capture start: {:?}",
                 capture_start.elapsed()
@@ -233,78 +411,136 @@ impl GPhotoCamera {
             info!("Preview already stopped, proceeding directly to capture");
         }
 
-        // Kill any lingering gphoto2 processes
-        info!("Killing any lingering gphoto2 processes...");
-        let kill_start = std::time::Instant::now();
-        Self::kill_gphoto_processes();
-        info!("Process kill complete in: {:?}", kill_start.elapsed());
+        self.resume_from_error().map_err(CameraError::Busy)?;
+        self.state
+            .transition(CameraState::Capturing, "capture requested")
+            .map_err(CameraError::Busy)?;
 
-        info!("Waiting 200ms for process cleanup...");
-        tokio::time::sleep(Duration::from_millis(200)).await;
-        info!(
-            "Total elapsed since capture start: {:?}",
-            capture_start.elapsed()
-        );
+        match self.capture_on_session(output_path, capture_start).await {
+            Ok(jpeg_data) => {
+                let _ = self.state.transition(CameraState::Idle, "capture complete");
+                Ok(jpeg_data)
+            }
+            Err(e) => {
+                self.state.fail(&e.to_string());
+                Err(e)
+            }
+        }
+    }
 
-        // Capture photo using gphoto2
-        info!("Executing gphoto2 capture command...");
-        info!(
-            "Command: gphoto2 --capture-image-and-download --filename {} --force-overwrite",
-            output_path
-        );
-        let capture_cmd_start = std::time::Instant::now();
+    async fn status(&self) -> CameraStatus {
+        let is_connected = self.session.lock().await.is_some();
+        let snapshot = self.state.snapshot();
+        // Live view only reaches the loopback device while our ffmpeg is alive
+        let pipeline_alive = self
+            .preview_process
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|p| !p.has_exited());
+        CameraStatus {
+            backend: self.type_name(),
+            model: self.model.lock().unwrap().clone(),
+            state: snapshot.state,
+            is_connected,
+            is_streaming: snapshot.state == CameraState::Previewing && pipeline_alive,
+            error_message: snapshot
+                .last_error
+                .filter(|_| snapshot.state == CameraState::Error),
+            processes: self.processes.running(),
+        }
+    }
+
+    /// Read a single config widget (e.g. "iso", "shutterspeed") as a string
+    async fn get_config_value(&self, key: &str) -> Result<String, String> {
+        let guard = self.session.lock().await;
+        let camera = guard
+            .as_ref()
+            .ok_or_else(|| "Camera not connected".to_string())?;
 
-        let output = tokio::process::Command::new("gphoto2")
-            .args(&[
-                "--capture-image-and-download",
-                "--filename",
-                output_path,
-                "--force-overwrite",
-            ])
-            .output()
+        let widget = camera
+            .config_key::<Widget>(key)
             .await
-            .map_err(|e| {
-                let elapsed = capture_cmd_start.elapsed();
-                error!("Failed to run capture command after {:?}: {}", elapsed, e);
-                format!("Failed to run capture command: {}", e)
-            })?;
+            .map_err(|e| format!("Failed to read config '{}': {}", key, e))?;
 
-        let capture_cmd_duration = capture_cmd_start.elapsed();
-        info!("Capture command completed in: {:?}", capture_cmd_duration);
-        info!(
-            "Total elapsed since capture start: {:?}",
-            capture_start.elapsed()
-        );
+        Ok(widget_value(&widget))
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            error!("Capture failed with exit code: {:?}", output.status.code());
-            error!("STDERR: {}", stderr);
-            error!("STDOUT: {}", stdout);
-            error!("Total time before failure: {:?}", capture_start.elapsed());
+    /// Write a single config widget and push it to the camera
+    async fn set_config_value(&self, key: &str, value: &str) -> Result<(), String> {
+        let guard = self.session.lock().await;
+        let camera = guard
+            .as_ref()
+            .ok_or_else(|| "Camera not connected".to_string())?;
 
-            // Check for specific error patterns
-            if stderr.contains("Device Busy") || stderr.contains("PTP Device Busy") {
-                error!("Camera is busy - may need longer delay after stopping preview");
-            }
-            if stderr.contains("I/O in progress") {
-                error!("I/O operation in progress - camera still processing previous command");
-            }
+        let widget = camera
+            .config_key::<Widget>(key)
+            .await
+            .map_err(|e| format!("Failed to read config '{}': {}", key, e))?;
 
-            return Err(format!("Failed to capture photo: {}", stderr));
+        if widget.readonly() {
+            return Err(format!("Config '{}' is read-only", key));
         }
 
-        info!("=== CAPTURE PHOTO SUCCESS ===");
-        info!("Photo captured successfully: {}", output_path);
-        info!("Total capture time: {:?}", capture_start.elapsed());
+        match &widget {
+            Widget::Radio(w) => w
+                .set_choice(value)
+                .map_err(|e| format!("Invalid choice '{}' for '{}': {}", value, key, e))?,
+            Widget::Text(w) => w
+                .set_value(value)
+                .map_err(|e| format!("Invalid value '{}' for '{}': {}", value, key, e))?,
+            Widget::Range(w) => {
+                let number = value
+                    .parse::<f32>()
+                    .map_err(|_| format!("Config '{}' expects a number, got '{}'", key, value))?;
+                w.set_value(number);
+            }
+            Widget::Toggle(w) => w.set_toggled(matches!(value, "1" | "true" | "on")),
+            _ => return Err(format!("Config '{}' cannot be set", key)),
+        }
 
-        // Read the captured file
-        let jpeg_data = tokio::fs::read(output_path)
+        camera
+            .set_config(&widget)
             .await
-            .map_err(|e| format!("Failed to read captured photo: {}", e))?;
+            .map_err(|e| format!("Failed to write config '{}': {}", key, e))?;
 
-        Ok(jpeg_data)
+        debug!("Camera config '{}' set to '{}'", key, value);
+        Ok(())
+    }
+
+    /// Stopping live view waits out the ffmpeg shutdown and then the settle delay
+    fn prepare_lead_time(&self) -> Duration {
+        let timing = self.timing();
+        Duration::from_millis(
+            timing.sigterm_grace_ms
+                + timing.kill_wait_ms
+                + timing.post_preview_stop_ms
+                + PREVIEW_STOP_OVERHEAD_MS,
+        )
+    }
+
+    fn preview_frames(&self) -> PreviewFrames {
+        Box::pin(read_loopback_frames(
+            self.processes.clone(),
+            self.config.v4l2_loopback_device.clone(),
+            self.config.preview_stream,
+        ))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "Canon EOS (gphoto2)"
+    }
+
+    async fn shutdown(&self) {
+        if let Some(task) = self.preview_task.lock().unwrap().take() {
+            task.abort();
+        }
+        // The preview process is in the supervisor too; stop everything we started at once
+        let process = self.preview_process.lock().unwrap().take();
+        self.processes
+            .shutdown(Duration::from_millis(self.timing().sigterm_grace_ms))
+            .await;
+        drop(process);
     }
 }
 
@@ -312,29 +548,277 @@ impl Drop for GPhotoCamera {
     fn drop(&mut self) {
         info!("GPhotoCamera dropping, cleaning up processes...");
 
-        // Set streaming flag to false
-        *self.is_streaming.lock().unwrap() = false;
+        if let Some(task) = self.preview_task.lock().unwrap().take() {
+            task.abort();
+        }
 
-        // Kill the preview process if it exists
-        if let Some(mut process) = self.preview_process.lock().unwrap().take() {
-            info!("Cleaning up preview process on drop");
+        // Drop may run on a runtime thread, so don't wait here: `shutdown` gives the
+        // children their grace period, this only makes sure none outlive the camera
+        self.processes.kill_all();
 
-            // Try to get the process ID
-            let pid = process.id();
-            // Kill the entire process group
-            unsafe {
-                libc::kill(-(pid as i32), libc::SIGTERM);
-                std::thread::sleep(Duration::from_millis(100));
-                libc::kill(-(pid as i32), libc::SIGKILL);
-            }
+        info!("GPhotoCamera cleanup complete");
+    }
+}
 
-            let _ = process.kill();
-            let _ = process.wait();
+/// Map a libgphoto2 failure onto the app's camera error kinds
+fn camera_error(e: gphoto2::Error) -> CameraError {
+    let message = e.to_string();
+    match e.kind() {
+        ErrorKind::CameraBusy | ErrorKind::IoLock | ErrorKind::IoUsbClaim => {
+            CameraError::Busy(message)
+        }
+        ErrorKind::Timeout => {
+            CameraError::IoError(std::io::Error::new(std::io::ErrorKind::TimedOut, message))
+        }
+        ErrorKind::Io
+        | ErrorKind::IoRead
+        | ErrorKind::IoWrite
+        | ErrorKind::IoUpdate
+        | ErrorKind::IoUsbClearHalt => CameraError::IoError(std::io::Error::other(message)),
+        ErrorKind::IoUsbFind | ErrorKind::ModelNotFound | ErrorKind::UnknownPort => {
+            CameraError::DeviceNotFound { device: message }
         }
+        _ => CameraError::CaptureError(message),
+    }
+}
 
-        // Kill any remaining gphoto2/ffmpeg processes
-        Self::kill_gphoto_processes();
+/// Current value of a config widget as a string
+fn widget_value(widget: &Widget) -> String {
+    match widget {
+        Widget::Radio(w) => w.choice(),
+        Widget::Text(w) => w.value(),
+        Widget::Range(w) => w.value().to_string(),
+        Widget::Toggle(w) => match w.toggled() {
+            Some(true) => "1".to_string(),
+            Some(false) => "0".to_string(),
+            None => String::new(),
+        },
+        Widget::Date(w) => w.timestamp().to_string(),
+        Widget::Group(_) | Widget::Button(_) => String::new(),
+    }
+}
 
-        info!("GPhotoCamera cleanup complete");
+/// Forward a child's stderr into the log, one line at a time, until it exits
+fn log_child_stderr(name: &'static str, stderr: ChildStderr) {
+    tokio::spawn(async move {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            warn!("{} stderr: {}", name, line);
+        }
+        debug!("{} stderr closed", name);
+    });
+}
+
+/// Pull live view frames off the camera and write them into ffmpeg until streaming stops
+async fn run_live_view(
+    context: gphoto2::Context,
+    session: Arc<tokio::sync::Mutex<Option<gphoto2::Camera>>>,
+    state: Arc<CameraStateMachine>,
+    mut ffmpeg_stdin: ChildStdin,
+) {
+    let start_time = std::time::Instant::now();
+    let mut frame_count = 0u64;
+
+    loop {
+        if state.current() != CameraState::Previewing {
+            break;
+        }
+
+        let frame = {
+            let guard = session.lock().await;
+            let Some(camera) = guard.as_ref() else {
+                warn!("Camera session closed, stopping live view");
+                state.fail("camera session closed during live view");
+                break;
+            };
+            match camera.capture_preview().await {
+                Ok(file) => file.get_data(&context).await,
+                Err(e) => Err(e),
+            }
+        };
+
+        match frame {
+            Ok(data) => {
+                if let Err(e) = ffmpeg_stdin.write_all(&data).await {
+                    warn!("Live view pipe to ffmpeg closed: {}", e);
+                    if state.current() == CameraState::Previewing {
+                        state.fail(&format!("live view pipe closed: {}", e));
+                    }
+                    break;
+                }
+                frame_count += 1;
+                if frame_count % 300 == 1 {
+                    debug!(
+                        "Live view: {} frames, {:.1} FPS",
+                        frame_count,
+                        frame_count as f32 / start_time.elapsed().as_secs_f32()
+                    );
+                }
+            }
+            Err(e) => {
+                warn!("Live view frame failed: {} ({:?})", e, e.kind());
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    debug!("Live view loop exited after {} frames", frame_count);
+}
+
+/// ffmpeg's MJPEG `-q:v` (2 best - 31 worst) for a JPEG quality of 1-100
+fn mjpeg_qscale(quality: u8) -> u32 {
+    2 + (100 - quality.clamp(1, 100) as u32) * 29 / 99
+}
+
+/// Start the ffmpeg that reads the loopback device as MJPEG at the configured rate and
+/// quality, handing back the process and its stdout
+fn spawn_loopback_reader(
+    processes: &ProcessSupervisor,
+    v4l2_device: &str,
+    stream: &PreviewStreamConfig,
+) -> Result<(ManagedChild, ChildStdout), CameraError> {
+    let fps = stream.fps.to_string();
+    // Without a configured quality keep ffmpeg's usual preview setting
+    let qscale = stream.quality.map_or(5, mjpeg_qscale).to_string();
+    let mut cmd = tokio::process::Command::new("ffmpeg");
+    cmd.args([
+        "-f",
+        "v4l2",
+        "-i",
+        v4l2_device,
+        "-f",
+        "mjpeg",
+        "-q:v",
+        &qscale,
+        "-r",
+        &fps,
+        "-", // Output to stdout
+    ])
+    .stdin(std::process::Stdio::null())
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped());
+
+    // Killed with its group if the stream is dropped mid-read
+    info!(
+        "Spawning ffmpeg for MJPEG stream from {} at {} fps, -q:v {}",
+        v4l2_device, fps, qscale
+    );
+    let mut process = processes
+        .spawn("ffmpeg (loopback reader)", cmd)
+        .map_err(CameraError::StreamStartError)?;
+    let stdout = process.stdout.take().ok_or_else(|| {
+        CameraError::StreamStartError("ffmpeg (loopback reader) has no stdout".to_string())
+    })?;
+    if let Some(stderr) = process.stderr.take() {
+        log_child_stderr("ffmpeg (loopback reader)", stderr);
+    }
+    Ok((process, stdout))
+}
+
+/// Read MJPEG frames back out of the v4l2loopback device that the preview pipeline feeds
+fn read_loopback_frames(
+    processes: Arc<ProcessSupervisor>,
+    v4l2_device: String,
+    stream: PreviewStreamConfig,
+) -> impl futures::Stream<Item = Bytes> + Send {
+    async_stream::stream! {
+        info!("Starting direct preview stream from {}", v4l2_device);
+
+        let (process, stdout) = match spawn_loopback_reader(&processes, &v4l2_device, &stream) {
+            Ok(reader) => reader,
+            Err(e) => {
+                // The broadcaster reopens the source after a pause
+                error!("Failed to start preview stream: {}", e);
+                return;
+            }
+        };
+
+        let mut reader = tokio::io::BufReader::new(stdout);
+        info!("Starting MJPEG stream parsing");
+
+        // MJPEG stream parsing
+        const JPEG_START: &[u8] = &[0xFF, 0xD8];
+        const JPEG_END: &[u8] = &[0xFF, 0xD9];
+
+        let mut buffer = Vec::with_capacity(1024 * 1024); // 1MB buffer
+        let mut jpeg_buffer = Vec::new();
+        let mut in_jpeg = false;
+        let mut total_bytes = 0usize;
+        let mut frame_count = 0u32;
+        let start_time = std::time::Instant::now();
+
+        use tokio::io::AsyncReadExt;
+
+        loop {
+            let mut chunk = vec![0u8; 65536]; // 64KB chunks
+            match reader.read(&mut chunk).await {
+                Ok(0) => {
+                    warn!("Preview stream ended");
+                    break;
+                }
+                Ok(n) => {
+                    total_bytes += n;
+                    if total_bytes < 1000 {
+                        debug!("Read {} bytes from stream (total: {})", n, total_bytes);
+                    }
+                    buffer.extend_from_slice(&chunk[..n]);
+
+                    // Look for JPEG markers
+                    let mut i = 0;
+                    while i < buffer.len() {
+                        if !in_jpeg {
+                            // Look for JPEG start
+                            if i + 1 < buffer.len() && buffer[i] == JPEG_START[0] && buffer[i+1] == JPEG_START[1] {
+                                in_jpeg = true;
+                                jpeg_buffer.clear();
+                                jpeg_buffer.push(buffer[i]);
+                                jpeg_buffer.push(buffer[i+1]);
+                                i += 2;
+                            } else {
+                                i += 1;
+                            }
+                        } else {
+                            // Look for JPEG end
+                            if i + 1 < buffer.len() && buffer[i] == JPEG_END[0] && buffer[i+1] == JPEG_END[1] {
+                                jpeg_buffer.push(buffer[i]);
+                                jpeg_buffer.push(buffer[i+1]);
+
+                                // We have a complete JPEG frame
+                                frame_count += 1;
+                                if frame_count % 30 == 1 {  // Log every 30th frame
+                                    let elapsed = start_time.elapsed();
+                                    info!("Streaming: {} frames, {} bytes, {:.1} FPS",
+                                         frame_count, total_bytes,
+                                         frame_count as f32 / elapsed.as_secs_f32());
+                                }
+
+                                yield Bytes::from(std::mem::take(&mut jpeg_buffer));
+
+                                in_jpeg = false;
+                                i += 2;
+                            } else {
+                                jpeg_buffer.push(buffer[i]);
+                                i += 1;
+                            }
+                        }
+                    }
+
+                    // Keep unprocessed bytes
+                    if in_jpeg {
+                        buffer.clear();
+                    } else {
+                        buffer.drain(..i);
+                    }
+                }
+                Err(e) => {
+                    error!("Error reading preview stream: {}", e);
+                    error!("Read {} bytes total before error", total_bytes);
+                    break;
+                }
+            }
+        }
+
+        process.stop(Duration::from_millis(100), Duration::from_secs(1)).await;
     }
 }
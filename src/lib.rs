@@ -9,14 +9,56 @@ pub mod session;
 // Template functionality
 pub mod templates;
 
+// Print layouts loaded from TOML/JSON files
+pub mod template_layout;
+
+// Wrapping, shrinking and ellipsizing text to fit its box
+pub mod text_layout;
+
+// Sepia, grain, vignette and torn-edge photo effects
+pub mod photo_effects;
+
+// Print sizes, sheets and cutting
+pub mod print_format;
+
+// Font lookup by family and style, with a built-in fallback
+pub mod fonts;
+
 pub mod errors;
 
 // Configuration module
 pub mod config;
 
+// Camera abstraction and backends
+pub mod camera;
+
+// Server-driven capture countdown
+pub mod capture_scheduler;
+
+// Named camera config presets
+pub mod camera_preset;
+
+// Camera lifecycle state machine
+pub mod camera_state;
+
+// Camera hot-plug supervisor
+pub mod camera_supervisor;
+
 // GPhoto2 camera functionality
 pub mod gphoto_camera;
 
+// EXIF orientation, mounting rotation and mirroring
+pub mod orientation;
+
+// Ownership and cleanup of spawned child processes
+pub mod process_supervisor;
+
+// Shared live view frame producer
+pub mod preview_broadcaster;
+
+// Frozen-preview detection and restart
+pub mod preview_watchdog;
+
 // Re-export commonly used types for convenience
 pub use printers::{
     new_printer, MockPrinter, PaperSize, PrintJob, PrintQuality, Printer, PrinterError,
@@ -26,8 +68,13 @@ pub use printers::{
 #[cfg(feature = "printer-cups")]
 pub use printers::CupsPrinter;
 
+// Camera exports
+pub use camera::{new_camera, Camera, CameraStatus, MockCamera};
+pub use camera_preset::CameraPreset;
+pub use camera_state::{CameraState, CameraStateMachine};
+
 // Session exports
-pub use session::Session;
+pub use session::{Session, SessionPhoto};
 
 // Template exports
-pub use templates::{create_templated_print_with_background, PrintTemplate, TemplateError};
+pub use templates::{PrintTemplate, TemplateError};
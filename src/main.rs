@@ -7,16 +7,37 @@ use std::sync::{Arc, Mutex};
 use tracing::{error, info, warn};
 
 // Module imports
+mod camera;
+mod camera_preset;
+mod camera_state;
+mod camera_supervisor;
+mod capture_scheduler;
 mod config;
 mod errors;
+mod fonts;
 mod gphoto_camera;
+mod orientation;
+mod photo_effects;
+mod preview_broadcaster;
+mod preview_watchdog;
+mod print_format;
 mod printers;
+mod process_supervisor;
 mod routes;
 mod session;
+mod template_layout;
 mod templates;
+mod text_layout;
 
+use camera::SharedCamera;
+use camera_state::CameraStateMachine;
+use camera_supervisor::{CameraEvents, CameraSupervisor};
+use capture_scheduler::CaptureScheduler;
 use config::Config;
 use errors::AppError;
+use fonts::FontRegistry;
+use preview_broadcaster::PreviewBroadcaster;
+use preview_watchdog::PreviewWatchdog;
 
 // ============================================================================
 // Application State
@@ -27,7 +48,13 @@ use errors::AppError;
 pub struct AppState {
     pub config: Config,
     pub db_pool: SqlitePool,
-    pub camera: Arc<Mutex<Option<Arc<gphoto_camera::GPhotoCamera>>>>,
+    pub camera: SharedCamera,
+    pub camera_state: Arc<CameraStateMachine>,
+    pub camera_events: CameraEvents,
+    pub preview: Arc<PreviewBroadcaster>,
+    pub preview_watchdog: Arc<PreviewWatchdog>,
+    pub capture_scheduler: Arc<CaptureScheduler>,
+    pub fonts: Arc<FontRegistry>,
     pub printer: Option<Arc<dyn printers::Printer + Send + Sync>>,
 }
 
@@ -42,13 +69,41 @@ impl AppState {
         // Initialize printer (non-critical)
         let printer = Self::initialize_printer().await;
 
+        // Fonts are looked up once; prints fall back to the built-in font for any missing
+        let fonts = Arc::new(FontRegistry::new(&config.template.font_dirs));
+        for text in config.template_layout(config.template.print_format).text {
+            fonts.font_or_fallback(&text.font, text.style);
+        }
+
         // Camera will be initialized separately due to its async nature
-        let camera = Arc::new(Mutex::new(None));
+        let camera: SharedCamera = Arc::new(Mutex::new(None));
+        let camera_state = Arc::new(CameraStateMachine::new());
+        let camera_events = camera_supervisor::camera_events();
+
+        // All preview clients share one frame producer
+        let preview = Arc::new(PreviewBroadcaster::new(camera.clone()));
+        let capture_scheduler = Arc::new(CaptureScheduler::new(
+            camera.clone(),
+            db_pool.clone(),
+            preview.clone(),
+        ));
+        let preview_watchdog = Arc::new(PreviewWatchdog::new(
+            config.watchdog.clone(),
+            camera.clone(),
+            camera_state.clone(),
+            preview.clone(),
+        ));
 
         Ok(Self {
             config,
             db_pool,
             camera,
+            camera_state,
+            camera_events,
+            preview,
+            preview_watchdog,
+            capture_scheduler,
+            fonts,
             printer,
         })
     }
@@ -101,11 +156,15 @@ impl AppState {
 // Camera Initialization
 // ============================================================================
 
+/// Connect the camera if it's there and start the hot-plug supervisor either way
 async fn initialize_camera(
     config: config::CameraConfig,
-    camera_ref: Arc<Mutex<Option<Arc<gphoto_camera::GPhotoCamera>>>>,
-) -> Result<(), AppError> {
-    info!("Initializing GPhoto2 camera with config: {:?}", config);
+    camera_ref: SharedCamera,
+    camera_state: Arc<CameraStateMachine>,
+    camera_events: CameraEvents,
+    db_pool: SqlitePool,
+) -> tokio::task::JoinHandle<()> {
+    info!("Initializing camera with config: {:?}", config);
 
     // Override device to use v4l2loopback device if specified
     let mut camera_config = config.clone();
@@ -114,39 +173,16 @@ async fn initialize_camera(
         camera_config.v4l2_loopback_device = device;
     }
 
-    info!(
-        "GPhoto2 will stream preview to: {}",
-        camera_config.v4l2_loopback_device
-    );
-
-    // Create and initialize camera
-    let camera = gphoto_camera::GPhotoCamera::new(camera_config)
-        .map_err(|e| AppError::Initialization(format!("Failed to create GPhoto2 camera: {}", e)))?;
-
-    camera.initialize().await.map_err(|e| {
-        AppError::Initialization(format!("Failed to initialize GPhoto2 camera: {}", e))
-    })?;
-
-    info!("GPhoto2 camera initialized successfully");
-
-    let camera_arc = Arc::new(camera);
+    let supervisor = CameraSupervisor::new(camera_config, camera_ref, camera_state, camera_events)
+        .with_presets(db_pool);
 
-    // Store camera reference
-    {
-        let mut guard = camera_ref.lock().unwrap();
-        *guard = Some(camera_arc.clone());
+    // A missing camera is no longer fatal; the supervisor keeps looking for it
+    if let Err(e) = supervisor.connect().await {
+        warn!("Camera not available at startup: {}", e);
+        warn!("Starting in degraded mode, will keep trying to connect");
     }
 
-    // Start preview stream in background
-    let camera_for_stream = camera_arc.clone();
-    tokio::spawn(async move {
-        info!("Starting GPhoto2 camera preview stream");
-        if let Err(e) = camera_for_stream.start_preview_stream().await {
-            error!("GPhoto2 camera stream error: {}", e);
-        }
-    });
-
-    Ok(())
+    supervisor.spawn()
 }
 
 // ============================================================================
@@ -180,20 +216,19 @@ async fn cleanup_resources(state: AppState) {
     info!("Beginning resource cleanup");
 
     // Clean up camera
-    if let Some(camera) = state.camera.lock().unwrap().take() {
-        info!("Cleaning up GPhoto camera...");
-        // Dropping the Arc will trigger the Drop implementation
+    let camera = state.camera.lock().unwrap().take();
+    if let Some(camera) = camera {
+        info!("Cleaning up {}...", camera.type_name());
+        camera.shutdown().await;
         drop(camera);
-        // Allow time for cleanup
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     }
 
     // Close database connections
     state.db_pool.close().await;
     info!("Database connections closed");
 
-    // Note: We're not using pkill anymore - resources should clean up properly via Drop
-    // If we still have issues, we should fix the root cause rather than using pkill
+    // Child processes belong to the camera's process supervisor and were stopped with the
+    // camera; nothing else on the box is signalled
 
     info!("Resource cleanup complete");
 }
@@ -247,16 +282,18 @@ async fn main() -> std::io::Result<()> {
     // Phase 3: Camera Initialization
     // ========================================
 
-    // Initialize camera (critical component)
-    initialize_camera(config.camera.clone(), app_state.camera.clone())
-        .await
-        .map_err(|e| {
-            error!("Camera initialization failed: {}", e);
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Camera is required for photo booth operation: {}", e),
-            )
-        })?;
+    // Initialize camera (degraded mode if it isn't plugged in yet)
+    let camera_supervisor = initialize_camera(
+        config.camera.clone(),
+        app_state.camera.clone(),
+        app_state.camera_state.clone(),
+        app_state.camera_events.clone(),
+        app_state.db_pool.clone(),
+    )
+    .await;
+
+    // Restart live view whenever it freezes
+    let preview_watchdog = app_state.preview_watchdog.clone().spawn();
 
     // ========================================
     // Phase 4: HTTP Server Setup
@@ -274,7 +311,13 @@ async fn main() -> std::io::Result<()> {
             // Application state
             .app_data(web::Data::new(state.config.clone()))
             .app_data(web::Data::new(state.db_pool.clone()))
-            .app_data(web::Data::new(state.camera.clone()));
+            .app_data(web::Data::new(state.camera.clone()))
+            .app_data(web::Data::from(state.camera_state.clone()))
+            .app_data(web::Data::new(state.camera_events.clone()))
+            .app_data(web::Data::from(state.preview.clone()))
+            .app_data(web::Data::from(state.preview_watchdog.clone()))
+            .app_data(web::Data::from(state.capture_scheduler.clone()))
+            .app_data(web::Data::from(state.fonts.clone()));
 
         // Core routes
         app = app
@@ -295,10 +338,29 @@ async fn main() -> std::io::Result<()> {
             .service(routes::thank_you_page)
             // Camera functionality
             .service(routes::preview_stream)
+            .service(routes::preview_frame)
+            .service(routes::preview_settings)
+            .service(routes::camera_status)
+            .service(routes::camera_events)
+            .service(routes::metrics)
+            .service(routes::health_status)
             .service(routes::capture_image)
+            .service(routes::burst_settings)
+            .service(routes::capture_burst)
+            .service(routes::capture_gif)
+            .service(routes::schedule_capture)
+            .service(routes::capture_schedule_events)
             .service(routes::test_stream)
+            // Camera admin
+            .service(routes::get_camera_config)
+            .service(routes::update_camera_config)
+            .service(routes::list_camera_presets)
+            .service(routes::save_camera_preset)
+            .service(routes::delete_camera_preset)
+            .service(routes::apply_camera_preset)
             // Story generation
             .service(routes::generate_story)
+            .service(routes::set_framing)
             // Static file serving
             .service(fs::Files::new("/images", state.config.images_path()).show_files_listing())
             .service(
@@ -341,6 +403,12 @@ async fn main() -> std::io::Result<()> {
     // Stop accepting new connections and wait for existing ones to complete
     server_handle.stop(true).await;
 
+    // Stop reconnecting before the camera is torn down
+    camera_supervisor.abort();
+    if let Some(watchdog) = preview_watchdog {
+        watchdog.abort();
+    }
+
     // Clean up resources
     cleanup_resources(app_state).await;
 
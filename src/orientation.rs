@@ -0,0 +1,189 @@
+// Photo orientation: the camera's EXIF orientation tag plus the booth's own mounting
+// rotation and mirroring, applied before anything is templated
+
+use image::DynamicImage;
+use tracing::{debug, warn};
+
+use crate::config::OrientationConfig;
+
+// Re-encoded captures go to print
+const CAPTURE_QUALITY: u8 = 95;
+
+/// The EXIF orientation (1-8) of a JPEG, if it carries one
+pub fn exif_orientation(jpeg: &[u8]) -> Option<u8> {
+    if jpeg.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    // Walk the marker segments up to the image data looking for APP1 "Exif"
+    let mut pos = 2;
+    while pos + 4 <= jpeg.len() {
+        if jpeg[pos] != 0xFF {
+            return None;
+        }
+        let marker = jpeg[pos + 1];
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+        let segment = jpeg.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return tiff_orientation(&segment[6..]);
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// Orientation tag (0x0112) from the first IFD of an EXIF TIFF block
+fn tiff_orientation(tiff: &[u8]) -> Option<u8> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b = tiff.get(at..at + 2)?;
+        Some(if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let b = tiff.get(at..at + 4)?;
+        Some(if big_endian {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    for i in 0..entries {
+        let entry = ifd + 2 + i * 12;
+        if u16_at(entry)? == 0x0112 {
+            // A single SHORT sits at the start of the value field
+            let value = u16_at(entry + 8)?;
+            return (1..=8).contains(&value).then_some(value as u8);
+        }
+    }
+    None
+}
+
+/// Turn an image so that EXIF `orientation` becomes 1 (top-left)
+pub fn apply_exif_orientation(image: DynamicImage, orientation: u8) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Rotate clockwise by `rotation` degrees (0, 90, 180 or 270), then optionally mirror
+pub fn apply_mounting(image: DynamicImage, rotation: u16, mirror: bool) -> DynamicImage {
+    let image = match rotation {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image,
+    };
+    if mirror {
+        image.fliph()
+    } else {
+        image
+    }
+}
+
+/// A captured JPEG turned upright and mounted as configured. The bytes come back as they
+/// are when there is nothing to change, or if they can't be decoded
+pub fn orient_capture(jpeg: Vec<u8>, config: &OrientationConfig) -> Vec<u8> {
+    let exif = exif_orientation(&jpeg).unwrap_or(1);
+    if exif == 1 && config.rotation == 0 && !config.mirror_capture {
+        return jpeg;
+    }
+
+    let image = match image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg) {
+        Ok(image) => image,
+        Err(e) => {
+            warn!("Could not decode capture to orient it: {}", e);
+            return jpeg;
+        }
+    };
+    debug!(
+        "Orienting capture: EXIF {}, rotation {}, mirror {}",
+        exif, config.rotation, config.mirror_capture
+    );
+    let image = apply_exif_orientation(image, exif);
+    let image = apply_mounting(image, config.rotation, config.mirror_capture);
+
+    let mut out = Vec::new();
+    match image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, CAPTURE_QUALITY)
+        .encode_image(&image)
+    {
+        Ok(()) => out,
+        Err(e) => {
+            warn!("Could not re-encode oriented capture: {}", e);
+            jpeg
+        }
+    }
+}
+
+/// Open a photo the right way up according to its EXIF orientation
+pub fn open_upright(path: &str) -> Result<DynamicImage, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let image = image::load_from_memory(&data).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(match exif_orientation(&data) {
+        Some(orientation) => apply_exif_orientation(image, orientation),
+        None => image,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A JPEG with only an APP1 EXIF segment carrying `orientation`, little-endian
+    fn jpeg_with_orientation(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&0x0112u16.to_le_bytes());
+        tiff.extend_from_slice(&3u16.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        tiff.extend_from_slice(&orientation.to_le_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+        let mut segment = b"Exif\0\0".to_vec();
+        segment.extend_from_slice(&tiff);
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&((segment.len() + 2) as u16).to_be_bytes());
+        jpeg.extend_from_slice(&segment);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02]);
+        jpeg
+    }
+
+    #[test]
+    fn test_exif_orientation_is_read() {
+        assert_eq!(exif_orientation(&jpeg_with_orientation(6)), Some(6));
+        assert_eq!(exif_orientation(&jpeg_with_orientation(1)), Some(1));
+        assert_eq!(exif_orientation(&[0xFF, 0xD8, 0xFF, 0xDA, 0, 2]), None);
+        assert_eq!(exif_orientation(b"not a jpeg"), None);
+    }
+
+    #[test]
+    fn test_portrait_mount_turns_capture_upright() {
+        let landscape = DynamicImage::new_rgb8(30, 20);
+        let upright = apply_mounting(apply_exif_orientation(landscape, 6), 0, false);
+        assert_eq!((upright.width(), upright.height()), (20, 30));
+
+        let turned = apply_mounting(DynamicImage::new_rgb8(30, 20), 270, true);
+        assert_eq!((turned.width(), turned.height()), (20, 30));
+    }
+}
@@ -0,0 +1,315 @@
+// Aged-photo effects applied to each shot before it goes on the poster: sepia and duotone
+// toning, contrast curves, film grain, vignetting and torn paper edges. Anything random is
+// drawn from a seeded generator, so the same seed always gives the same print
+
+use image::{Rgb, RgbImage, Rgba, RgbaImage};
+use serde::Deserialize;
+
+use crate::template_layout::hex_color;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum PhotoEffect {
+    /// Brown-toned black and white; `amount` below 1.0 keeps some of the original colour
+    Sepia {
+        #[serde(default = "full")]
+        amount: f32,
+    },
+    /// Black and white remapped so shadows take one colour and highlights another
+    Duotone {
+        #[serde(deserialize_with = "hex_color")]
+        shadow: Rgb<u8>,
+        #[serde(deserialize_with = "hex_color")]
+        highlight: Rgb<u8>,
+    },
+    /// S-curve: 0.0 leaves the photo alone, 1.0 crushes shadows and highlights hard.
+    /// Negative amounts flatten it instead
+    Contrast { amount: f32 },
+    /// Monochrome film grain; `amount` is the strongest change as a fraction of full range
+    Grain { amount: f32 },
+    /// Darken towards the corners. Starts `radius` of the way out from the centre
+    Vignette {
+        strength: f32,
+        #[serde(default = "vignette_radius")]
+        radius: f32,
+    },
+    /// Ragged edges, up to `depth` pixels deep, that let the poster show through
+    TornEdges { depth: u32 },
+}
+
+fn full() -> f32 {
+    1.0
+}
+
+fn vignette_radius() -> f32 {
+    0.5
+}
+
+/// The look of the old-west poster: worn contrast, sepia, grain, dark corners and torn
+/// edges. Selected with TEMPLATE_EFFECTS=wanted_poster
+pub fn wanted_poster() -> Vec<PhotoEffect> {
+    vec![
+        PhotoEffect::Contrast { amount: 0.4 },
+        PhotoEffect::Sepia { amount: 0.9 },
+        PhotoEffect::Grain { amount: 0.08 },
+        PhotoEffect::Vignette {
+            strength: 0.6,
+            radius: 0.45,
+        },
+        PhotoEffect::TornEdges { depth: 18 },
+    ]
+}
+
+/// Named effect chains for TEMPLATE_EFFECTS
+pub fn preset(name: &str) -> Option<Vec<PhotoEffect>> {
+    match name.trim().to_lowercase().as_str() {
+        "none" | "" => Some(Vec::new()),
+        "wanted_poster" => Some(wanted_poster()),
+        _ => None,
+    }
+}
+
+/// Run `effects` over `photo` in order. Transparent pixels are where torn edges cut
+/// the photo away
+pub fn apply_effects(photo: &RgbImage, effects: &[PhotoEffect], seed: u64) -> RgbaImage {
+    let mut image = RgbaImage::from_fn(photo.width(), photo.height(), |x, y| {
+        let Rgb([r, g, b]) = *photo.get_pixel(x, y);
+        Rgba([r, g, b, 255])
+    });
+    for (i, effect) in effects.iter().enumerate() {
+        // Each effect gets its own stream, so adding one doesn't reshuffle the others
+        let mut rng = SplitMix64::new(seed ^ (i as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        match *effect {
+            PhotoEffect::Sepia { amount } => sepia(&mut image, amount),
+            PhotoEffect::Duotone { shadow, highlight } => duotone(&mut image, shadow, highlight),
+            PhotoEffect::Contrast { amount } => contrast(&mut image, amount),
+            PhotoEffect::Grain { amount } => grain(&mut image, amount, &mut rng),
+            PhotoEffect::Vignette { strength, radius } => vignette(&mut image, strength, radius),
+            PhotoEffect::TornEdges { depth } => torn_edges(&mut image, depth, &mut rng),
+        }
+    }
+    image
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+fn channel(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn sepia(image: &mut RgbaImage, amount: f32) {
+    let amount = amount.clamp(0.0, 1.0);
+    for pixel in image.pixels_mut() {
+        let [r, g, b, _] = pixel.0.map(|c| c as f32);
+        let toned = [
+            0.393 * r + 0.769 * g + 0.189 * b,
+            0.349 * r + 0.686 * g + 0.168 * b,
+            0.272 * r + 0.534 * g + 0.131 * b,
+        ];
+        for c in 0..3 {
+            pixel[c] = channel(pixel[c] as f32 + (toned[c] - pixel[c] as f32) * amount);
+        }
+    }
+}
+
+fn duotone(image: &mut RgbaImage, shadow: Rgb<u8>, highlight: Rgb<u8>) {
+    for pixel in image.pixels_mut() {
+        let t = luma(pixel) / 255.0;
+        for c in 0..3 {
+            pixel[c] = channel(shadow[c] as f32 * (1.0 - t) + highlight[c] as f32 * t);
+        }
+    }
+}
+
+fn contrast(image: &mut RgbaImage, amount: f32) {
+    let curve: Vec<u8> = (0..256)
+        .map(|v| {
+            let x = v as f32 / 255.0;
+            let s_curve = x * x * (3.0 - 2.0 * x);
+            channel((x + (s_curve - x) * amount) * 255.0)
+        })
+        .collect();
+    for pixel in image.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = curve[pixel[c] as usize];
+        }
+    }
+}
+
+fn grain(image: &mut RgbaImage, amount: f32, rng: &mut SplitMix64) {
+    let strength = amount.clamp(0.0, 1.0) * 255.0;
+    for pixel in image.pixels_mut() {
+        // Two uniform draws give softer, film-like triangular noise
+        let noise = (rng.next_f32() + rng.next_f32() - 1.0) * strength;
+        for c in 0..3 {
+            pixel[c] = channel(pixel[c] as f32 + noise);
+        }
+    }
+}
+
+fn vignette(image: &mut RgbaImage, strength: f32, radius: f32) {
+    let (width, height) = image.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let max_distance = (cx * cx + cy * cy).sqrt().max(1.0);
+    let radius = radius.clamp(0.0, 0.99);
+    let strength = strength.clamp(0.0, 1.0);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let distance = (dx * dx + dy * dy).sqrt() / max_distance;
+        let t = ((distance - radius) / (1.0 - radius)).clamp(0.0, 1.0);
+        let darken = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+        for c in 0..3 {
+            pixel[c] = channel(pixel[c] as f32 * darken);
+        }
+    }
+}
+
+/// Cut each edge along a random walk, so the tear wanders in and out rather than
+/// looking like noise
+fn torn_edges(image: &mut RgbaImage, depth: u32, rng: &mut SplitMix64) {
+    let (width, height) = image.dimensions();
+    let depth = depth.min(width / 4).min(height / 4);
+    if depth == 0 {
+        return;
+    }
+
+    let mut tear = |length: u32| -> Vec<u32> {
+        let mut position = rng.next_f32() * depth as f32;
+        (0..length)
+            .map(|_| {
+                position = (position + (rng.next_f32() - 0.5) * 3.0).clamp(1.0, depth as f32);
+                position.round() as u32
+            })
+            .collect()
+    };
+    let (top, bottom) = (tear(width), tear(width));
+    let (left, right) = (tear(height), tear(height));
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if y < top[x as usize]
+            || height - 1 - y < bottom[x as usize]
+            || x < left[y as usize]
+            || width - 1 - x < right[y as usize]
+        {
+            pixel[3] = 0;
+        }
+    }
+}
+
+/// Small, fast and stable across platforms and crate versions, which a print that must
+/// come out the same again needs more than statistical quality
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(120, 80, |x, y| {
+            Rgb([(x * 2) as u8, (y * 3) as u8, ((x + y) % 256) as u8])
+        })
+    }
+
+    #[test]
+    fn test_effects_are_deterministic_for_a_seed() {
+        let photo = gradient();
+        let chain = wanted_poster();
+        assert_eq!(
+            apply_effects(&photo, &chain, 42),
+            apply_effects(&photo, &chain, 42)
+        );
+        assert_ne!(
+            apply_effects(&photo, &chain, 42),
+            apply_effects(&photo, &chain, 43)
+        );
+    }
+
+    #[test]
+    fn test_tones_vignette_and_torn_edges() {
+        let photo = RgbImage::from_pixel(100, 100, Rgb([40, 120, 220]));
+
+        // Sepia turns blue warm
+        let toned = apply_effects(&photo, &[PhotoEffect::Sepia { amount: 1.0 }], 0);
+        let Rgba([r, _, b, a]) = *toned.get_pixel(50, 50);
+        assert!(r > b);
+        assert_eq!(a, 255);
+
+        // Vignette leaves the centre and darkens the corners
+        let vignetted = apply_effects(
+            &photo,
+            &[PhotoEffect::Vignette {
+                strength: 0.8,
+                radius: 0.3,
+            }],
+            0,
+        );
+        assert_eq!(vignetted.get_pixel(50, 50)[2], 220);
+        assert!(vignetted.get_pixel(0, 0)[2] < 120);
+
+        // Torn edges cut into every side but leave the middle
+        let torn = apply_effects(&photo, &[PhotoEffect::TornEdges { depth: 10 }], 7);
+        assert_eq!(torn.get_pixel(0, 50)[3], 0);
+        assert_eq!(torn.get_pixel(99, 50)[3], 0);
+        assert_eq!(torn.get_pixel(50, 0)[3], 0);
+        assert_eq!(torn.get_pixel(50, 99)[3], 0);
+        assert_eq!(torn.get_pixel(50, 50)[3], 255);
+    }
+
+    #[test]
+    fn test_effects_parse_from_layout_toml() {
+        #[derive(Deserialize)]
+        struct Chain {
+            effects: Vec<PhotoEffect>,
+        }
+        let chain: Chain = toml::from_str(
+            r##"
+            [[effects]]
+            effect = "duotone"
+            shadow = "#20100a"
+            highlight = "#f0e0c0"
+
+            [[effects]]
+            effect = "vignette"
+            strength = 0.5
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            chain.effects,
+            vec![
+                PhotoEffect::Duotone {
+                    shadow: Rgb([0x20, 0x10, 0x0a]),
+                    highlight: Rgb([0xf0, 0xe0, 0xc0]),
+                },
+                PhotoEffect::Vignette {
+                    strength: 0.5,
+                    radius: 0.5,
+                },
+            ]
+        );
+    }
+}
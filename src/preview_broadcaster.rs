@@ -0,0 +1,514 @@
+// Single producer for live view frames, fanned out to every /preview client
+
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::camera::SharedCamera;
+use crate::templates::{PHOTO_HEIGHT, PHOTO_WIDTH};
+
+// Only the newest frames matter; slow clients skip ahead instead of queueing
+const FRAME_BUFFER: usize = 2;
+// Delay before reopening the frame source after it ends or the camera is missing
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+// Longest wait for a frame before re-checking which camera is connected
+const SOURCE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+// A cached frame younger than this is served to single-frame requests as is
+const MAX_FRAME_AGE: Duration = Duration::from_secs(1);
+// Longest a single-frame request waits for the producer to deliver one
+const FRAME_WAIT: Duration = Duration::from_secs(5);
+// Oldest live view frame that may stand in for a failed capture; live view is off
+// for the capture and all its retries, so this has to cover both
+const FALLBACK_MAX_FRAME_AGE: Duration = Duration::from_secs(60);
+// Fallback photos go to print, so keep as much of the upscaled frame as we can
+const FALLBACK_QUALITY: u8 = 95;
+
+type LatestFrame = Arc<Mutex<Option<(Instant, Bytes)>>>;
+/// Width and JPEG quality of a re-encoded stream
+type Variant = (Option<u32>, u8);
+type Variants = Arc<Mutex<HashMap<Variant, broadcast::Sender<Bytes>>>>;
+
+pub struct PreviewBroadcaster {
+    camera: SharedCamera,
+    sender: broadcast::Sender<Bytes>,
+    producer_running: Arc<Mutex<bool>>,
+    latest: LatestFrame,
+    // Resized streams that have clients, each encoded once for all of them
+    variants: Variants,
+}
+
+impl PreviewBroadcaster {
+    pub fn new(camera: SharedCamera) -> Self {
+        let (sender, _) = broadcast::channel(FRAME_BUFFER);
+        PreviewBroadcaster {
+            camera,
+            sender,
+            producer_running: Arc::new(Mutex::new(false)),
+            latest: Arc::new(Mutex::new(None)),
+            variants: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Subscribe to live view frames, starting the producer if this is the first client
+    pub fn subscribe(&self) -> Result<broadcast::Receiver<Bytes>, String> {
+        if self.camera.lock().unwrap().is_none() {
+            return Err("camera not available".to_string());
+        }
+
+        // Subscribe while holding the flag so the producer can't exit in between
+        let mut running = self.producer_running.lock().unwrap();
+        let receiver = self.sender.subscribe();
+
+        if !*running {
+            info!("Starting preview producer");
+            *running = true;
+            tokio::spawn(run_producer(
+                self.camera.clone(),
+                self.sender.clone(),
+                self.producer_running.clone(),
+                self.latest.clone(),
+            ));
+        }
+
+        debug!(
+            "Preview clients connected: {}",
+            self.sender.receiver_count()
+        );
+        Ok(receiver)
+    }
+
+    /// Subscribe to frames scaled down to `width` and re-encoded at `quality`. Every
+    /// distinct width and quality is encoded once, however many clients watch it
+    pub fn subscribe_scaled(
+        &self,
+        width: Option<u32>,
+        quality: u8,
+    ) -> Result<broadcast::Receiver<Bytes>, String> {
+        let key = (width, quality);
+        let mut variants = self.variants.lock().unwrap();
+        if let Some(sender) = variants.get(&key) {
+            return Ok(sender.subscribe());
+        }
+
+        let source = self.subscribe()?;
+        let (sender, receiver) = broadcast::channel(FRAME_BUFFER);
+        variants.insert(key, sender.clone());
+        info!(
+            "Starting preview scaler for width {:?}, quality {}",
+            width, quality
+        );
+        tokio::spawn(run_scaler(source, sender, self.variants.clone(), key));
+        Ok(receiver)
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// The newest live view frame. Served from cache while a stream is running;
+    /// otherwise the producer runs just long enough to deliver one
+    pub async fn latest_frame(&self) -> Result<Bytes, String> {
+        if let Some((at, frame)) = self.latest.lock().unwrap().clone() {
+            if at.elapsed() <= MAX_FRAME_AGE {
+                return Ok(frame);
+            }
+        }
+
+        let mut frames = self.subscribe()?;
+        loop {
+            match tokio::time::timeout(FRAME_WAIT, frames.recv()).await {
+                Ok(Ok(frame)) => return Ok(frame),
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) => return Err("preview stopped".to_string()),
+                Err(_) => return Err("no preview frame available".to_string()),
+            }
+        }
+    }
+
+    /// Collect live view frames for `duration`, keeping at most `fps` of them per second
+    pub async fn record(&self, duration: Duration, fps: u32) -> Result<Vec<Bytes>, String> {
+        let mut frames = self.subscribe()?;
+        let deadline = tokio::time::Instant::now() + duration;
+        let mut recorded = Vec::new();
+        let mut limiter = FrameLimiter::new(fps);
+
+        loop {
+            match tokio::time::timeout_at(deadline, frames.recv()).await {
+                Ok(Ok(frame)) => {
+                    if limiter.admit(Instant::now()) {
+                        recorded.push(frame);
+                    }
+                }
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) => break,
+                Err(_) => break,
+            }
+        }
+
+        if recorded.is_empty() {
+            return Err("no preview frames arrived while recording".to_string());
+        }
+        debug!("Recorded {} live view frame(s)", recorded.len());
+        Ok(recorded)
+    }
+
+    /// The last good live view frame, upscaled to the template photo size, for when a
+    /// full-resolution capture fails. Never starts the producer: live view is off by now
+    pub fn fallback_photo(&self) -> Result<Vec<u8>, String> {
+        let (at, frame) = self
+            .latest
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| "no live view frame seen yet".to_string())?;
+        if at.elapsed() > FALLBACK_MAX_FRAME_AGE {
+            return Err(format!("last live view frame is {:?} old", at.elapsed()));
+        }
+
+        let frame = image::load_from_memory_with_format(&frame, image::ImageFormat::Jpeg)
+            .map_err(|e| format!("Failed to decode live view frame: {}", e))?;
+        let photo = frame.resize_to_fill(
+            PHOTO_WIDTH,
+            PHOTO_HEIGHT,
+            image::imageops::FilterType::Lanczos3,
+        );
+
+        let mut out = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, FALLBACK_QUALITY)
+            .encode_image(&photo)
+            .map_err(|e| format!("Failed to encode fallback photo: {}", e))?;
+        Ok(out)
+    }
+}
+
+/// Read frames from the camera once and broadcast them until the last client leaves
+async fn run_producer(
+    camera: SharedCamera,
+    sender: broadcast::Sender<Bytes>,
+    running: Arc<Mutex<bool>>,
+    latest: LatestFrame,
+) {
+    let has_clients = || {
+        let mut running = running.lock().unwrap();
+        if sender.receiver_count() == 0 {
+            *running = false;
+            false
+        } else {
+            true
+        }
+    };
+
+    'producer: loop {
+        let current = camera.lock().unwrap().clone();
+        let Some(current) = current else {
+            warn!("Preview producer waiting for camera");
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            if !has_clients() {
+                break;
+            }
+            continue;
+        };
+
+        let mut frames = current.preview_frames();
+        loop {
+            // Wake up now and then so a reconnected or unplugged camera is noticed
+            match tokio::time::timeout(SOURCE_CHECK_INTERVAL, frames.next()).await {
+                Ok(Some(frame)) => {
+                    *latest.lock().unwrap() = Some((Instant::now(), frame.clone()));
+                    if sender.send(frame).is_err() && !has_clients() {
+                        break 'producer;
+                    }
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    if !has_clients() {
+                        break 'producer;
+                    }
+                }
+            }
+
+            let same_camera = camera
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|c| Arc::ptr_eq(c, &current));
+            if !same_camera {
+                info!("Camera changed, reopening preview source");
+                break;
+            }
+        }
+
+        debug!("Preview frame source ended");
+        tokio::time::sleep(RECONNECT_DELAY).await;
+        if !has_clients() {
+            break;
+        }
+    }
+
+    info!("No preview clients left, preview producer stopped");
+}
+
+/// Re-encode the producer's frames for one variant until its last client leaves
+async fn run_scaler(
+    mut source: broadcast::Receiver<Bytes>,
+    sender: broadcast::Sender<Bytes>,
+    variants: Variants,
+    key: Variant,
+) {
+    let (width, quality) = key;
+    // Checked under the map's lock, so no client can subscribe to a scaler that is leaving
+    let leave_if_unwatched = || {
+        let mut variants = variants.lock().unwrap();
+        if sender.receiver_count() == 0 {
+            variants.remove(&key);
+            true
+        } else {
+            false
+        }
+    };
+
+    loop {
+        let jpeg = match tokio::time::timeout(SOURCE_CHECK_INTERVAL, source.recv()).await {
+            Ok(Ok(jpeg)) => jpeg,
+            Ok(Err(RecvError::Lagged(_))) => continue,
+            Ok(Err(RecvError::Closed)) => {
+                variants.lock().unwrap().remove(&key);
+                break;
+            }
+            Err(_) => {
+                if leave_if_unwatched() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let resized =
+            tokio::task::spawn_blocking(move || resize_frame(&jpeg, width, None, quality)).await;
+        let frame = match resized {
+            Ok(Ok(frame)) => Bytes::from(frame),
+            Ok(Err(e)) => {
+                warn!("{}", e);
+                continue;
+            }
+            Err(_) => {
+                variants.lock().unwrap().remove(&key);
+                break;
+            }
+        };
+        if sender.send(frame).is_err() && leave_if_unwatched() {
+            break;
+        }
+    }
+
+    debug!(
+        "Preview scaler for width {:?}, quality {} stopped",
+        width, quality
+    );
+}
+
+/// Thins a frame sequence down to a target rate. Frames are kept on an even schedule,
+/// so 5 fps off a 30 fps source really is 5 fps rather than every 7th frame
+pub struct FrameLimiter {
+    spacing: Duration,
+    next_due: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: u32) -> Self {
+        FrameLimiter {
+            spacing: Duration::from_secs(1) / fps.max(1),
+            next_due: None,
+        }
+    }
+
+    /// Whether a frame arriving at `now` should be kept
+    pub fn admit(&mut self, now: Instant) -> bool {
+        let Some(due) = self.next_due else {
+            self.next_due = Some(now + self.spacing);
+            return true;
+        };
+
+        // A little slack so source jitter doesn't drop frames at matching rates
+        if now + self.spacing / 4 < due {
+            return false;
+        }
+        // After a gap, start the schedule again instead of sending a burst to catch up
+        self.next_due = Some(if now > due + self.spacing {
+            now + self.spacing
+        } else {
+            due + self.spacing
+        });
+        true
+    }
+}
+
+/// Scale a JPEG frame to fit `width` x `height` (either may be omitted to keep the
+/// aspect ratio) and re-encode it at `quality`
+pub fn resize_frame(
+    jpeg: &[u8],
+    width: Option<u32>,
+    height: Option<u32>,
+    quality: u8,
+) -> Result<Vec<u8>, String> {
+    let frame = image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("Failed to decode preview frame: {}", e))?;
+
+    let (src_width, src_height) = (frame.width().max(1), frame.height().max(1));
+    let (width, height) = match (width, height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, w * src_height / src_width),
+        (None, Some(h)) => (h * src_width / src_height, h),
+        (None, None) => (src_width, src_height),
+    };
+
+    // Only ever scale down; upscaling a preview frame just wastes bandwidth
+    let frame = if width < src_width || height < src_height {
+        frame.resize(
+            width.max(1),
+            height.max(1),
+            image::imageops::FilterType::Triangle,
+        )
+    } else {
+        frame
+    };
+
+    let mut out = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100))
+        .encode_image(&frame)
+        .map_err(|e| format!("Failed to encode preview frame: {}", e))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::tests::mock_config;
+    use crate::camera::{Camera, MockCamera};
+    use crate::camera_state::CameraStateMachine;
+    use crate::config::CameraConfig;
+
+    #[tokio::test]
+    async fn test_clients_share_one_producer() {
+        let dir = std::env::temp_dir().join(format!("preview_bcast_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.jpg"), b"frame").unwrap();
+
+        let camera = MockCamera::new(
+            &CameraConfig {
+                mock_preview_fps: 50,
+                ..mock_config(&dir)
+            },
+            Arc::new(CameraStateMachine::new()),
+        );
+        camera.initialize().await.unwrap();
+        camera.start_preview().await.unwrap();
+
+        let shared: SharedCamera = Arc::new(Mutex::new(None));
+        let broadcaster = PreviewBroadcaster::new(shared.clone());
+        assert!(broadcaster.subscribe().is_err());
+
+        *shared.lock().unwrap() = Some(Arc::new(camera));
+        let mut first = broadcaster.subscribe().unwrap();
+        let mut second = broadcaster.subscribe().unwrap();
+        assert_eq!(broadcaster.client_count(), 2);
+
+        assert_eq!(&first.recv().await.unwrap()[..], b"frame");
+        assert_eq!(&second.recv().await.unwrap()[..], b"frame");
+        // Served from the cache the stream keeps fresh
+        assert_eq!(&broadcaster.latest_frame().await.unwrap()[..], b"frame");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_scaled_clients_share_one_encoder() {
+        let dir = std::env::temp_dir().join(format!("preview_scaled_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        image::DynamicImage::new_rgb8(640, 480)
+            .save(dir.join("a.jpg"))
+            .unwrap();
+
+        let camera = MockCamera::new(
+            &CameraConfig {
+                mock_preview_fps: 50,
+                ..mock_config(&dir)
+            },
+            Arc::new(CameraStateMachine::new()),
+        );
+        camera.initialize().await.unwrap();
+        camera.start_preview().await.unwrap();
+        let broadcaster = PreviewBroadcaster::new(Arc::new(Mutex::new(Some(Arc::new(camera)))));
+
+        let mut first = broadcaster.subscribe_scaled(Some(320), 60).unwrap();
+        let mut second = broadcaster.subscribe_scaled(Some(320), 60).unwrap();
+        let _other = broadcaster.subscribe_scaled(Some(160), 60).unwrap();
+        assert_eq!(broadcaster.variants.lock().unwrap().len(), 2);
+
+        for frames in [&mut first, &mut second] {
+            let frame = image::load_from_memory(&frames.recv().await.unwrap()).unwrap();
+            assert_eq!((frame.width(), frame.height()), (320, 240));
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_resize_frame_keeps_aspect_ratio() {
+        let frame = image::DynamicImage::new_rgb8(640, 480);
+        let mut jpeg = Vec::new();
+        frame
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageOutputFormat::Jpeg(90),
+            )
+            .unwrap();
+
+        let small = resize_frame(&jpeg, Some(320), None, 60).unwrap();
+        let small = image::load_from_memory(&small).unwrap();
+        assert_eq!((small.width(), small.height()), (320, 240));
+    }
+
+    #[test]
+    fn test_frame_limiter_thins_to_target_rate() {
+        let start = Instant::now();
+        let source = |fps: u64, secs: u64| {
+            (0..fps * secs).map(move |i| start + Duration::from_millis(i * 1000 / fps))
+        };
+
+        let mut limiter = FrameLimiter::new(5);
+        let kept = source(30, 4).filter(|at| limiter.admit(*at)).count();
+        // 4 seconds at 5 fps, give or take the frame on either edge
+        assert!((20..=21).contains(&kept), "kept {}", kept);
+
+        // Asking for more than the source delivers keeps everything
+        let mut limiter = FrameLimiter::new(30);
+        let kept = source(30, 4).filter(|at| limiter.admit(*at)).count();
+        assert_eq!(kept, 120);
+    }
+
+    #[test]
+    fn test_fallback_photo_upscales_last_frame() {
+        let broadcaster = PreviewBroadcaster::new(Arc::new(Mutex::new(None)));
+        assert!(broadcaster.fallback_photo().is_err());
+
+        let frame = image::DynamicImage::new_rgb8(320, 240);
+        let mut jpeg = Vec::new();
+        frame
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageOutputFormat::Jpeg(90),
+            )
+            .unwrap();
+        *broadcaster.latest.lock().unwrap() = Some((Instant::now(), Bytes::from(jpeg)));
+
+        let photo = broadcaster.fallback_photo().unwrap();
+        let photo = image::load_from_memory(&photo).unwrap();
+        assert_eq!((photo.width(), photo.height()), (PHOTO_WIDTH, PHOTO_HEIGHT));
+    }
+}
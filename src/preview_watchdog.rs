@@ -0,0 +1,242 @@
+// Frozen-preview watchdog: measures the frame rate coming off the loopback device and
+// restarts the live view pipeline, with backoff, when frames stop arriving
+
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::camera::SharedCamera;
+use crate::camera_state::{CameraState, CameraStateMachine};
+use crate::config::WatchdogConfig;
+use crate::preview_broadcaster::PreviewBroadcaster;
+
+// How often the watchdog wakes up when no frames arrive
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+// Window the reported frame rate is averaged over
+const FPS_WINDOW: Duration = Duration::from_secs(5);
+
+/// Live view health as reported by /camera/status and /metrics
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreviewHealth {
+    /// Frames per second arriving from the loopback device over the last few seconds
+    pub fps: f32,
+    pub frames_total: u64,
+    pub last_frame_at: Option<String>,
+    /// Live view is on but no frames have arrived within the stall timeout
+    pub stalled: bool,
+    pub restarts: u64,
+    /// Restarts where the pipeline itself failed to come back up
+    pub failed_restarts: u64,
+    pub last_restart_at: Option<String>,
+    pub last_restart_reason: Option<String>,
+}
+
+pub struct PreviewWatchdog {
+    config: WatchdogConfig,
+    camera: SharedCamera,
+    state: Arc<CameraStateMachine>,
+    preview: Arc<PreviewBroadcaster>,
+    health: Mutex<PreviewHealth>,
+}
+
+impl PreviewWatchdog {
+    pub fn new(
+        config: WatchdogConfig,
+        camera: SharedCamera,
+        state: Arc<CameraStateMachine>,
+        preview: Arc<PreviewBroadcaster>,
+    ) -> Self {
+        PreviewWatchdog {
+            config,
+            camera,
+            state,
+            preview,
+            health: Mutex::new(PreviewHealth::default()),
+        }
+    }
+
+    pub fn health(&self) -> PreviewHealth {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Watch the preview for as long as the app runs. Keeps one preview subscription
+    /// open, so the loopback device is always being read and the latest frame stays fresh
+    pub fn spawn(self: Arc<Self>) -> Option<JoinHandle<()>> {
+        if !self.config.enabled {
+            info!("Preview watchdog disabled");
+            return None;
+        }
+        info!(
+            "Preview watchdog restarting live view after {}s without frames",
+            self.config.stall_secs
+        );
+        Some(tokio::spawn(async move { self.run().await }))
+    }
+
+    async fn run(&self) {
+        let stall_after = Duration::from_secs(self.config.stall_secs);
+        let mut frames = None;
+        let mut last_frame = Instant::now();
+        let mut window_start = Instant::now();
+        let mut window_frames = 0u64;
+        // Consecutive restarts that haven't brought frames back
+        let mut restarts_in_row = 0u32;
+        let mut next_restart = Instant::now();
+
+        loop {
+            let Some(receiver) = frames.as_mut() else {
+                match self.preview.subscribe() {
+                    Ok(receiver) => frames = Some(receiver),
+                    Err(e) => {
+                        debug!("Preview watchdog waiting: {}", e);
+                        tokio::time::sleep(CHECK_INTERVAL).await;
+                    }
+                }
+                last_frame = Instant::now();
+                continue;
+            };
+
+            let result = tokio::time::timeout(CHECK_INTERVAL, receiver.recv()).await;
+            let received = match result {
+                Ok(Ok(_)) => 1,
+                // Frames we skipped still arrived on the device
+                Ok(Err(RecvError::Lagged(skipped))) => skipped,
+                Ok(Err(RecvError::Closed)) => {
+                    frames = None;
+                    0
+                }
+                Err(_) => 0,
+            };
+
+            if received > 0 {
+                last_frame = Instant::now();
+                window_frames += received;
+                if restarts_in_row > 0 {
+                    info!("Preview frames flowing again");
+                    restarts_in_row = 0;
+                }
+                let mut health = self.health.lock().unwrap();
+                health.frames_total += received;
+                health.last_frame_at = Some(Utc::now().to_rfc3339());
+                health.stalled = false;
+            }
+
+            if window_start.elapsed() >= FPS_WINDOW {
+                let fps = window_frames as f32 / window_start.elapsed().as_secs_f32();
+                self.health.lock().unwrap().fps = fps;
+                window_start = Instant::now();
+                window_frames = 0;
+            }
+
+            // Frames are only expected while live view is on; captures stop it on purpose
+            if self.state.current() != CameraState::Previewing {
+                last_frame = Instant::now();
+                continue;
+            }
+
+            if last_frame.elapsed() < stall_after {
+                continue;
+            }
+            self.health.lock().unwrap().stalled = true;
+            if Instant::now() < next_restart {
+                continue;
+            }
+
+            restarts_in_row += 1;
+            let reason = format!("no preview frames for {:?}", last_frame.elapsed());
+            self.restart_preview(&reason, restarts_in_row).await;
+
+            let backoff = self.config.backoff(restarts_in_row);
+            debug!("Next preview restart no sooner than {:?}", backoff);
+            next_restart = Instant::now() + backoff;
+            last_frame = Instant::now();
+        }
+    }
+
+    /// Tear down and rebuild the live view pipeline
+    async fn restart_preview(&self, reason: &str, attempt: u32) {
+        let Some(camera) = self.camera.lock().unwrap().clone() else {
+            return;
+        };
+
+        warn!(
+            "Preview frozen ({}), restarting live view (attempt {})",
+            reason, attempt
+        );
+        {
+            let mut health = self.health.lock().unwrap();
+            health.restarts += 1;
+            health.last_restart_at = Some(Utc::now().to_rfc3339());
+            health.last_restart_reason = Some(reason.to_string());
+        }
+
+        if let Err(e) = camera.stop_preview().await {
+            warn!("Could not stop frozen preview: {}", e);
+        }
+        if let Err(e) = camera.start_preview().await {
+            warn!("Failed to restart live view: {}", e);
+            self.health.lock().unwrap().failed_restarts += 1;
+            self.state.fail(&format!("preview restart failed: {}", e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::tests::mock_config;
+    use crate::camera::{Camera, MockCamera};
+    use crate::config::CameraConfig;
+
+    #[tokio::test]
+    async fn test_frozen_preview_is_restarted() {
+        let dir = std::env::temp_dir().join(format!("watchdog_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.jpg"), b"frame").unwrap();
+
+        let state = Arc::new(CameraStateMachine::new());
+        let camera = MockCamera::new(
+            &CameraConfig {
+                mock_preview_fps: 20,
+                ..mock_config(&dir)
+            },
+            state.clone(),
+        );
+        camera.initialize().await.unwrap();
+        camera.start_preview().await.unwrap();
+
+        let shared: SharedCamera = Arc::new(Mutex::new(Some(Arc::new(camera))));
+        let preview = Arc::new(PreviewBroadcaster::new(shared.clone()));
+        let watchdog = Arc::new(PreviewWatchdog::new(
+            WatchdogConfig {
+                enabled: true,
+                stall_secs: 1,
+                restart_backoff_secs: 0,
+                max_restart_backoff_secs: 0,
+            },
+            shared,
+            state.clone(),
+            preview,
+        ));
+        watchdog.clone().spawn().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let health = watchdog.health();
+        assert!(health.frames_total > 0);
+        assert_eq!(health.restarts, 0);
+
+        // Frames stop arriving while live view is still on
+        std::fs::remove_file(dir.join("a.jpg")).unwrap();
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+        let health = watchdog.health();
+        assert!(health.restarts >= 1);
+        assert!(health.last_restart_reason.is_some());
+        assert_eq!(state.current(), CameraState::Previewing);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
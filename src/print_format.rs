@@ -0,0 +1,126 @@
+// Print formats: the size of one print, the paper it goes on and how many fit per sheet
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::printers::PaperSize;
+
+// Layouts and sheets are rendered at the printers' native resolution
+pub const PRINT_DPI: u32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PrintFormat {
+    /// One 4x6" poster per sheet
+    #[default]
+    #[serde(rename = "4x6")]
+    Photo4x6,
+    /// One 5x7" poster per sheet
+    #[serde(rename = "5x7")]
+    Photo5x7,
+    /// Two identical 2x6" strips side by side on a 4x6" sheet, cut apart by the printer
+    #[serde(rename = "2x6")]
+    Strip2x6,
+    /// Two identical 4x6" posters on a 6x8" sheet, cut apart by the printer
+    #[serde(rename = "4x6-2up")]
+    Photo4x6TwoUp,
+}
+
+impl PrintFormat {
+    pub const ALL: [PrintFormat; 4] = [
+        PrintFormat::Photo4x6,
+        PrintFormat::Photo5x7,
+        PrintFormat::Strip2x6,
+        PrintFormat::Photo4x6TwoUp,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            PrintFormat::Photo4x6 => "4x6",
+            PrintFormat::Photo5x7 => "5x7",
+            PrintFormat::Strip2x6 => "2x6",
+            PrintFormat::Photo4x6TwoUp => "4x6-2up",
+        }
+    }
+
+    /// Pixel size of one print, portrait
+    pub fn copy_size(&self) -> (u32, u32) {
+        match self {
+            PrintFormat::Photo4x6 | PrintFormat::Photo4x6TwoUp => (4 * PRINT_DPI, 6 * PRINT_DPI),
+            PrintFormat::Photo5x7 => (5 * PRINT_DPI, 7 * PRINT_DPI),
+            PrintFormat::Strip2x6 => (2 * PRINT_DPI, 6 * PRINT_DPI),
+        }
+    }
+
+    /// Pixel size of the sheet sent to the printer, portrait
+    pub fn sheet_size(&self) -> (u32, u32) {
+        match self {
+            PrintFormat::Photo4x6 | PrintFormat::Strip2x6 => (4 * PRINT_DPI, 6 * PRINT_DPI),
+            PrintFormat::Photo5x7 => (5 * PRINT_DPI, 7 * PRINT_DPI),
+            PrintFormat::Photo4x6TwoUp => (6 * PRINT_DPI, 8 * PRINT_DPI),
+        }
+    }
+
+    pub fn copies_per_sheet(&self) -> u32 {
+        match self {
+            PrintFormat::Photo4x6 | PrintFormat::Photo5x7 => 1,
+            PrintFormat::Strip2x6 | PrintFormat::Photo4x6TwoUp => 2,
+        }
+    }
+
+    pub fn paper_size(&self) -> PaperSize {
+        match self {
+            PrintFormat::Photo4x6 | PrintFormat::Strip2x6 => PaperSize::Photo4x6,
+            PrintFormat::Photo5x7 => PaperSize::Photo5x7,
+            PrintFormat::Photo4x6TwoUp => PaperSize::Photo6x8,
+        }
+    }
+
+    /// Whether the printer should cut each sheet in half
+    pub fn cut_in_half(&self) -> bool {
+        self.copies_per_sheet() == 2
+    }
+}
+
+impl fmt::Display for PrintFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl std::str::FromStr for PrintFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        PrintFormat::ALL
+            .into_iter()
+            .find(|format| format.id() == s)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown print format '{}' (expected 4x6, 5x7, 2x6 or 4x6-2up)",
+                    s
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_round_trip_and_fill_their_sheet() {
+        for format in PrintFormat::ALL {
+            assert_eq!(format.id().parse::<PrintFormat>().unwrap(), format);
+
+            // The copies cover the sheet exactly, turned sideways if need be
+            let (copy_w, copy_h) = format.copy_size();
+            let (sheet_w, sheet_h) = format.sheet_size();
+            assert_eq!(
+                copy_w * copy_h * format.copies_per_sheet(),
+                sheet_w * sheet_h
+            );
+        }
+        assert!("6x4".parse::<PrintFormat>().is_err());
+    }
+}
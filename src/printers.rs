@@ -17,6 +17,7 @@ pub enum PaperSize {
     A4,
     Photo4x6,
     Photo5x7,
+    Photo6x8,
     Custom(String),
 }
 
@@ -34,6 +35,8 @@ pub struct PrintJob {
     pub copies: u32,
     pub paper_size: PaperSize,
     pub quality: PrintQuality,
+    /// Cut each sheet in half (two strips or two posters per sheet)
+    pub cut_in_half: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -235,23 +238,40 @@ impl CupsPrinter {
         None
     }
 
-    fn get_paper_size_string(&self, paper_size: &PaperSize) -> String {
+    fn get_paper_size_string(&self, paper_size: &PaperSize, cut_in_half: bool) -> String {
+        let is_dnp = self.config.primary_name.contains("DNP");
+        // Gutenprint's "-div2" page sizes are what make a DNP cut the sheet in two
+        let cut = if cut_in_half && is_dnp { "-div2" } else { "" };
+        if cut_in_half && !is_dnp {
+            warn!(
+                "{} can't cut sheets; they will need cutting by hand",
+                self.printer_name
+            );
+        }
+
         match paper_size {
             PaperSize::Photo4x6 => {
                 // For DNP printers, use specific size format
-                if self.config.primary_name.contains("DNP") {
-                    "w288h432".to_string() // 4x6 inches at 72 DPI
+                if is_dnp {
+                    format!("w288h432{}", cut) // 4x6 inches at 72 DPI
                 } else {
                     "Borderless4x6in".to_string()
                 }
             }
             PaperSize::Photo5x7 => {
-                if self.config.primary_name.contains("DNP") {
+                if is_dnp {
                     "w360h504".to_string() // 5x7 inches at 72 DPI
                 } else {
                     "Borderless5x7in".to_string()
                 }
             }
+            PaperSize::Photo6x8 => {
+                if is_dnp {
+                    format!("w432h576{}", cut) // 6x8 inches at 72 DPI
+                } else {
+                    "Custom.6x8in".to_string()
+                }
+            }
             PaperSize::Letter => "Letter".to_string(),
             PaperSize::A4 => "A4".to_string(),
             PaperSize::Custom(size) => size.clone(),
@@ -329,7 +349,7 @@ impl Printer for CupsPrinter {
         let mut raw_properties = Vec::new();
 
         // Paper size
-        let paper_size_str = self.get_paper_size_string(&job.paper_size);
+        let paper_size_str = self.get_paper_size_string(&job.paper_size, job.cut_in_half);
         raw_properties.push(("PageSize", paper_size_str.clone()));
         debug!("Paper size: {}", paper_size_str);
 
@@ -422,8 +442,11 @@ pub struct MockPrinter;
 impl Printer for MockPrinter {
     async fn print_photo(&self, job: PrintJob) -> Result<String, PrinterError> {
         info!(
-            "MockPrinter: Simulating print of {} ({} copies)",
-            job.file_path, job.copies
+            "MockPrinter: Simulating print of {} ({} copies, {:?}{})",
+            job.file_path,
+            job.copies,
+            job.paper_size,
+            if job.cut_in_half { ", cut in half" } else { "" }
         );
 
         // Simulate some processing time
@@ -0,0 +1,233 @@
+// Owns the child processes the booth spawns (the ffmpeg halves of the preview pipeline).
+// Each child runs in its own process group, its exit is watched and reaped, and stopping
+// it signals only that group - never whatever else on the box matches a name
+
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub name: &'static str,
+    /// Also the process group id; every child leads its own group
+    pub pid: u32,
+    pub started_at: String,
+}
+
+type Running = Arc<Mutex<HashMap<u32, ProcessInfo>>>;
+
+#[derive(Default)]
+pub struct ProcessSupervisor {
+    // Children that haven't exited yet; the exit monitor removes them once reaped
+    running: Running,
+}
+
+impl ProcessSupervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn `cmd` in a new process group and watch it until it exits
+    pub fn spawn(&self, name: &'static str, mut cmd: Command) -> Result<ManagedChild, String> {
+        cmd.process_group(0);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", name, e))?;
+        let pid = child
+            .id()
+            .ok_or_else(|| format!("{} exited before it could be tracked", name))?;
+
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        self.running.lock().unwrap().insert(
+            pid,
+            ProcessInfo {
+                name,
+                pid,
+                started_at: Utc::now().to_rfc3339(),
+            },
+        );
+        info!("Started {} (PID {})", name, pid);
+
+        let (exited_tx, exited) = watch::channel(false);
+        let running = self.running.clone();
+        tokio::spawn(async move {
+            // Waiting here is what reaps the child, so none are left as zombies
+            let status = child.wait().await;
+            running.lock().unwrap().remove(&pid);
+            match status {
+                Ok(status) if status.success() => debug!("{} (PID {}) exited", name, pid),
+                Ok(status) => warn!("{} (PID {}) exited: {}", name, pid, status),
+                Err(e) => warn!("Lost track of {} (PID {}): {}", name, pid, e),
+            }
+            let _ = exited_tx.send(true);
+        });
+
+        Ok(ManagedChild {
+            name,
+            pid,
+            stdin,
+            stdout,
+            stderr,
+            exited,
+            running: self.running.clone(),
+        })
+    }
+
+    pub fn running(&self) -> Vec<ProcessInfo> {
+        let mut running: Vec<_> = self.running.lock().unwrap().values().cloned().collect();
+        running.sort_by_key(|p| p.pid);
+        running
+    }
+
+    /// Stop every child that is still running: SIGTERM, then SIGKILL whatever hasn't
+    /// exited after `grace`
+    pub async fn shutdown(&self, grace: Duration) {
+        let pids: Vec<u32> = self.running.lock().unwrap().keys().copied().collect();
+        if pids.is_empty() {
+            return;
+        }
+
+        info!("Stopping {} child process(es)", pids.len());
+        for &pid in &pids {
+            signal_group(&self.running, pid, libc::SIGTERM);
+        }
+        let deadline = tokio::time::Instant::now() + grace;
+        while !self.is_idle() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.kill_all();
+    }
+
+    /// SIGKILL every child that is still running. Doesn't wait, so it is safe from Drop
+    pub fn kill_all(&self) {
+        let pids: Vec<u32> = self.running.lock().unwrap().keys().copied().collect();
+        for pid in pids {
+            if signal_group(&self.running, pid, libc::SIGKILL) {
+                debug!("Killed group -{}", pid);
+            }
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.running.lock().unwrap().is_empty()
+    }
+}
+
+/// Signal a child's process group, but only while we still know it to be running, so a
+/// recycled PID is never hit
+fn signal_group(running: &Running, pid: u32, signal: i32) -> bool {
+    let running = running.lock().unwrap();
+    if !running.contains_key(&pid) {
+        return false;
+    }
+    unsafe {
+        libc::kill(-(pid as i32), signal);
+    }
+    true
+}
+
+/// A child owned by a `ProcessSupervisor`; its group is killed if this is dropped
+/// while it is still running
+pub struct ManagedChild {
+    name: &'static str,
+    pid: u32,
+    pub stdin: Option<ChildStdin>,
+    pub stdout: Option<ChildStdout>,
+    pub stderr: Option<ChildStderr>,
+    exited: watch::Receiver<bool>,
+    running: Running,
+}
+
+impl ManagedChild {
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn has_exited(&self) -> bool {
+        *self.exited.borrow()
+    }
+
+    /// SIGTERM the child's group, SIGKILL it if it is still around after `grace`, then
+    /// wait up to `kill_wait` for the exit to be reaped. Returns whether it exited
+    pub async fn stop(mut self, grace: Duration, kill_wait: Duration) -> bool {
+        debug!("Sending SIGTERM to {} group -{}", self.name, self.pid);
+        if signal_group(&self.running, self.pid, libc::SIGTERM)
+            && tokio::time::timeout(grace, self.exited.wait_for(|exited| *exited))
+                .await
+                .is_err()
+        {
+            debug!("Sending SIGKILL to {} group -{}", self.name, self.pid);
+            signal_group(&self.running, self.pid, libc::SIGKILL);
+        }
+
+        let exited = tokio::time::timeout(kill_wait, self.exited.wait_for(|exited| *exited))
+            .await
+            .is_ok();
+        if !exited {
+            warn!(
+                "{} (PID {}) still running after SIGKILL",
+                self.name, self.pid
+            );
+        }
+        exited
+    }
+}
+
+impl Drop for ManagedChild {
+    fn drop(&mut self) {
+        if signal_group(&self.running, self.pid, libc::SIGKILL) {
+            debug!("Killed {} (PID {}) on drop", self.name, self.pid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_children_are_stopped_and_reaped() {
+        let supervisor = ProcessSupervisor::new();
+
+        let mut quick = Command::new("true");
+        quick.stdin(std::process::Stdio::null());
+        let quick = supervisor.spawn("true", quick).unwrap();
+
+        let mut sleeper = Command::new("sleep");
+        sleeper.arg("30");
+        let sleeper = supervisor.spawn("sleep", sleeper).unwrap();
+        let pid = sleeper.pid();
+        assert!(supervisor.running().iter().any(|p| p.pid == pid));
+
+        let started = std::time::Instant::now();
+        assert!(
+            sleeper
+                .stop(Duration::from_millis(500), Duration::from_secs(2))
+                .await
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Exits on its own and is reaped without anyone waiting on it
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(quick.has_exited());
+        assert!(supervisor.running().is_empty());
+
+        // Shutdown returns as soon as the children are gone rather than after the grace
+        let mut sleeper = Command::new("sleep");
+        sleeper.arg("30");
+        let _sleeper = supervisor.spawn("sleep", sleeper).unwrap();
+        let started = std::time::Instant::now();
+        supervisor.shutdown(Duration::from_secs(5)).await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(supervisor.running().is_empty());
+    }
+}
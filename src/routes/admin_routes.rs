@@ -0,0 +1,248 @@
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json;
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::camera::{write_settings, Camera, SharedCamera};
+use crate::camera_preset::CameraPreset;
+
+// Settings shown when the request doesn't name any
+const DEFAULT_CONFIG_KEYS: [&str; 6] = [
+    "iso",
+    "aperture",
+    "shutterspeed",
+    "whitebalance",
+    "exposurecompensation",
+    "imageformat",
+];
+
+#[derive(Debug, Deserialize)]
+pub struct CameraSettingsRequest {
+    /// Config widget name -> value, e.g. "iso" -> "400"
+    pub settings: BTreeMap<String, String>,
+    /// Also push the preset to the camera and make it the one applied on connect
+    #[serde(default)]
+    pub apply: bool,
+}
+
+fn connected_camera(camera: &SharedCamera) -> Result<Arc<dyn Camera>, HttpResponse> {
+    camera.lock().unwrap().clone().ok_or_else(|| {
+        HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "ok": false,
+            "error": "Camera not connected"
+        }))
+    })
+}
+
+/// Push settings to the camera, reporting which ones it rejected
+async fn write_and_read_back(
+    camera: &dyn Camera,
+    settings: &BTreeMap<String, String>,
+) -> HttpResponse {
+    let failed = write_settings(camera, settings).await;
+
+    let mut values = BTreeMap::new();
+    for key in settings.keys() {
+        if let Ok(value) = camera.get_config_value(key).await {
+            values.insert(key.clone(), value);
+        }
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": failed.is_empty(),
+        "config": values,
+        "failed": failed,
+    }))
+}
+
+/// Read camera settings; `?keys=iso,aperture` picks which ones
+#[get("/admin/camera/config")]
+pub async fn get_camera_config(
+    camera: web::Data<SharedCamera>,
+    query: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let camera = match connected_camera(&camera) {
+        Ok(camera) => camera,
+        Err(response) => return response,
+    };
+
+    let keys: Vec<String> = match query.get("keys") {
+        Some(keys) => keys
+            .split(',')
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty())
+            .collect(),
+        None => DEFAULT_CONFIG_KEYS.iter().map(|k| k.to_string()).collect(),
+    };
+
+    let mut values = BTreeMap::new();
+    let mut failed = BTreeMap::new();
+    for key in keys {
+        match camera.get_config_value(&key).await {
+            Ok(value) => {
+                values.insert(key, value);
+            }
+            Err(e) => {
+                failed.insert(key, e);
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "camera": camera.type_name(),
+        "config": values,
+        "failed": failed,
+    }))
+}
+
+/// Write camera settings, e.g. `{"settings": {"iso": "800"}}`
+#[put("/admin/camera/config")]
+pub async fn update_camera_config(
+    camera: web::Data<SharedCamera>,
+    body: web::Json<CameraSettingsRequest>,
+) -> impl Responder {
+    let camera = match connected_camera(&camera) {
+        Ok(camera) => camera,
+        Err(response) => return response,
+    };
+
+    info!("Updating camera config: {:?}", body.settings);
+    write_and_read_back(camera.as_ref(), &body.settings).await
+}
+
+#[get("/admin/camera/presets")]
+pub async fn list_camera_presets(db_pool: web::Data<SqlitePool>) -> impl Responder {
+    match CameraPreset::list(&db_pool).await {
+        Ok(presets) => HttpResponse::Ok().json(serde_json::json!({
+            "ok": true,
+            "presets": presets
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "ok": false,
+            "error": format!("Failed to list camera presets: {}", e)
+        })),
+    }
+}
+
+/// Create or replace a preset; with `"apply": true` it is also applied now and on every connect
+#[put("/admin/camera/presets/{name}")]
+pub async fn save_camera_preset(
+    path: web::Path<String>,
+    body: web::Json<CameraSettingsRequest>,
+    db_pool: web::Data<SqlitePool>,
+    camera: web::Data<SharedCamera>,
+) -> impl Responder {
+    let name = path.into_inner();
+    let body = body.into_inner();
+
+    if name.trim().is_empty() || body.settings.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "ok": false,
+            "error": "Preset needs a name and at least one setting"
+        }));
+    }
+
+    let preset = CameraPreset::new(&name, body.settings);
+    if let Err(e) = preset.save(&db_pool).await {
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "ok": false,
+            "error": format!("Failed to save camera preset: {}", e)
+        }));
+    }
+    info!("Saved camera preset '{}'", name);
+
+    if body.apply {
+        return activate_and_apply(&name, &db_pool, &camera).await;
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "preset": preset
+    }))
+}
+
+#[delete("/admin/camera/presets/{name}")]
+pub async fn delete_camera_preset(
+    path: web::Path<String>,
+    db_pool: web::Data<SqlitePool>,
+) -> impl Responder {
+    let name = path.into_inner();
+    match CameraPreset::delete(&name, &db_pool).await {
+        Ok(true) => HttpResponse::Ok().json(serde_json::json!({ "ok": true })),
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({
+            "ok": false,
+            "error": "Camera preset not found"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "ok": false,
+            "error": format!("Failed to delete camera preset: {}", e)
+        })),
+    }
+}
+
+/// Apply a preset to the camera now and keep applying it whenever the camera connects
+#[post("/admin/camera/presets/{name}/apply")]
+pub async fn apply_camera_preset(
+    path: web::Path<String>,
+    db_pool: web::Data<SqlitePool>,
+    camera: web::Data<SharedCamera>,
+) -> impl Responder {
+    activate_and_apply(&path.into_inner(), &db_pool, &camera).await
+}
+
+async fn activate_and_apply(
+    name: &str,
+    db_pool: &SqlitePool,
+    camera: &SharedCamera,
+) -> HttpResponse {
+    let preset = match CameraPreset::load(name, db_pool).await {
+        Ok(Some(preset)) => preset,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "ok": false,
+                "error": "Camera preset not found"
+            }))
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "ok": false,
+                "error": format!("Failed to load camera preset: {}", e)
+            }))
+        }
+    };
+
+    if let Err(e) = CameraPreset::activate(name, db_pool).await {
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "ok": false,
+            "error": format!("Failed to activate camera preset: {}", e)
+        }));
+    }
+
+    // Without a camera the preset still takes effect on the next connect
+    let camera = match connected_camera(camera) {
+        Ok(camera) => camera,
+        Err(_) => {
+            info!(
+                "Camera preset '{}' will be applied when the camera connects",
+                name
+            );
+            return HttpResponse::Ok().json(serde_json::json!({
+                "ok": true,
+                "preset": name,
+                "applied": false
+            }));
+        }
+    };
+
+    let failed = preset.apply(camera.as_ref()).await;
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": failed.is_empty(),
+        "preset": name,
+        "applied": true,
+        "failed": failed
+    }))
+}
@@ -1,172 +1,242 @@
 use actix_web::{get, post, web, HttpResponse, Responder};
 use async_stream;
 use bytes::Bytes;
+use serde::Deserialize;
 use serde_json;
 use sqlx::SqlitePool;
-use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 
 use tracing::{debug, error, info, warn};
 
-use crate::config::Config;
+// Longest countdown a client may ask for
+const MAX_COUNTDOWN_SECS: u64 = 30;
+// JPEG quality for resized preview frames when none is requested
+const DEFAULT_FRAME_QUALITY: u8 = 80;
+
+use crate::camera::{capture_shots, spawn_preview_restart, SharedCamera};
+use crate::camera_state::CameraStateMachine;
+use crate::camera_supervisor::CameraEvents;
+use crate::capture_scheduler::{CaptureScheduler, ScheduleError, ScheduleRequest};
+use crate::config::{Config, MAX_BURST_SHOTS, MAX_PREVIEW_FPS};
+use crate::errors::{AppError, CameraError};
+use crate::fonts::FontRegistry;
+use crate::orientation::apply_mounting;
+use crate::preview_broadcaster::{resize_frame, FrameLimiter, PreviewBroadcaster};
+use crate::preview_watchdog::PreviewWatchdog;
+use crate::session::{Session, SessionPhoto};
+use crate::templates::{boomerang, PrintTemplate};
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    pub fps: Option<u32>,
+    pub width: Option<u32>,
+    pub quality: Option<u8>,
+}
 
+/// Live view as MJPEG. `?fps=`, `?width=` and `?quality=` (1-100) override the
+/// configured defaults, so a tablet on poor Wi-Fi can take a thinner stream than the kiosk
 #[get("/preview")]
-pub async fn preview_stream(config: web::Data<Config>) -> impl Responder {
-    let v4l2_device = config.camera.v4l2_loopback_device.clone();
+pub async fn preview_stream(
+    preview: web::Data<PreviewBroadcaster>,
+    config: web::Data<Config>,
+    query: web::Query<PreviewQuery>,
+) -> impl Responder {
+    let defaults = config.camera.preview_stream;
+    let fps = query.fps.unwrap_or(defaults.fps).clamp(1, MAX_PREVIEW_FPS);
+    let width = query.width.or(defaults.width).filter(|w| *w > 0);
+    let quality = query.quality.or(defaults.quality).map(|q| q.clamp(1, 100));
+    // Frames only need re-encoding when they are resized or their quality changes; the
+    // broadcaster does that once for every client wanting the same size and quality
+    let subscribed = if width.is_some() || quality.is_some() {
+        preview.subscribe_scaled(width, quality.unwrap_or(DEFAULT_FRAME_QUALITY))
+    } else {
+        preview.subscribe()
+    };
+
+    let mut frames = match subscribed {
+        Ok(frames) => frames,
+        Err(e) => {
+            warn!("Preview requested but {}", e);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "ok": false,
+                "error": e
+            }));
+        }
+    };
+
+    debug!(
+        "Preview client: {} fps, width {:?}, quality {:?}",
+        fps, width, quality
+    );
 
     let stream = async_stream::stream! {
-        info!("Starting direct preview stream from {}", v4l2_device);
-
-        // Use ffmpeg to stream directly from v4l2 device as MJPEG
-        let mut cmd = tokio::process::Command::new("ffmpeg");
-        cmd.args(&[
-            "-f", "v4l2",
-            "-i", &v4l2_device,
-            "-f", "mjpeg",
-            "-q:v", "5",  // Quality setting (lower = better quality)
-            "-r", "30",   // Frame rate
-            "-"           // Output to stdout
-        ])
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
-
-        info!("Spawning ffmpeg process for MJPEG stream from {}", v4l2_device);
-        let mut process = match cmd.spawn() {
-            Ok(p) => {
-                info!("FFmpeg process started successfully, PID: {:?}", p.id());
-                p
-            },
-            Err(e) => {
-                error!("Failed to start ffmpeg for preview stream: {}", e);
-                error!("Command was: ffmpeg -f v4l2 -video_size 1920x1080 -i {} -f mjpeg -q:v 5 -r 30 -", v4l2_device);
-                return;
+        const BOUNDARY: &str = "frame";
+        let mut limiter = FrameLimiter::new(fps);
+
+        loop {
+            let jpeg = match frames.recv().await {
+                Ok(jpeg) => jpeg,
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("Preview client lagging, skipped {} frame(s)", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if !limiter.admit(std::time::Instant::now()) {
+                continue;
             }
-        };
 
-        let stdout = process.stdout.take().expect("Failed to get stdout");
-        let stderr = process.stderr.take().expect("Failed to get stderr");
+            let boundary_prefix = format!("--{}\r\n", BOUNDARY).into_bytes();
+            let header = b"Content-Type: image/jpeg\r\n\r\n";
+            let tail = b"\r\n";
+
+            let mut part = Vec::with_capacity(
+                boundary_prefix.len() + header.len() + jpeg.len() + tail.len()
+            );
+            part.extend_from_slice(&boundary_prefix);
+            part.extend_from_slice(header);
+            part.extend_from_slice(&jpeg);
+            part.extend_from_slice(tail);
 
-        // Spawn a task to log stderr output
-        tokio::spawn(async move {
-            use tokio::io::{AsyncBufReadExt, BufReader};
-            let mut stderr_reader = BufReader::new(stderr).lines();
-            while let Ok(Some(line)) = stderr_reader.next_line().await {
-                warn!("FFmpeg stderr: {}", line);
+            yield Ok::<Bytes, actix_web::Error>(Bytes::from(part));
+        }
+        debug!("Preview frame stream finished");
+    };
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "multipart/x-mixed-replace; boundary=frame"))
+        .streaming(stream)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FrameQuery {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub quality: Option<u8>,
+}
+
+/// Latest live view frame as a single JPEG, for dashboards, thumbnails and health checks.
+/// `?width=`/`?height=` scale it down, `?quality=` (1-100) re-encodes it
+#[get("/preview/frame.jpg")]
+pub async fn preview_frame(
+    preview: web::Data<PreviewBroadcaster>,
+    query: web::Query<FrameQuery>,
+) -> impl Responder {
+    let frame = match preview.latest_frame().await {
+        Ok(frame) => frame,
+        Err(e) => {
+            debug!("Preview frame requested but {}", e);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "ok": false,
+                "error": e
+            }));
+        }
+    };
+
+    let FrameQuery {
+        width,
+        height,
+        quality,
+    } = query.into_inner();
+
+    let body = if width.is_none() && height.is_none() && quality.is_none() {
+        frame
+    } else {
+        let quality = quality.unwrap_or(DEFAULT_FRAME_QUALITY);
+        match tokio::task::spawn_blocking(move || resize_frame(&frame, width, height, quality))
+            .await
+        {
+            Ok(Ok(jpeg)) => Bytes::from(jpeg),
+            Ok(Err(e)) => {
+                warn!("{}", e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "ok": false,
+                    "error": e
+                }));
             }
-        });
+            Err(_) => {
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "ok": false, "error": "join error" }))
+            }
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "image/jpeg"))
+        .insert_header(("Cache-Control", "no-store"))
+        .body(body)
+}
 
-        let mut reader = tokio::io::BufReader::new(stdout);
-        info!("Starting MJPEG stream parsing");
+/// How the kiosk should turn and mirror the live view it shows
+#[get("/preview/settings")]
+pub async fn preview_settings(config: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "rotation": config.camera.orientation.rotation,
+        "mirror": config.camera.orientation.mirror_preview,
+    }))
+}
 
-        // MJPEG stream parsing
-        const JPEG_START: &[u8] = &[0xFF, 0xD8];
-        const JPEG_END: &[u8] = &[0xFF, 0xD9];
-        const BOUNDARY: &str = "frame";
+#[get("/camera/status")]
+pub async fn camera_status(
+    camera: web::Data<SharedCamera>,
+    camera_state: web::Data<CameraStateMachine>,
+    watchdog: web::Data<PreviewWatchdog>,
+) -> impl Responder {
+    let camera = camera.lock().unwrap().clone();
+    let details = match camera {
+        Some(camera) => Some(camera.status().await),
+        None => None,
+    };
 
-        let mut buffer = Vec::with_capacity(1024 * 1024); // 1MB buffer
-        let mut jpeg_buffer = Vec::new();
-        let mut in_jpeg = false;
-        let mut total_bytes = 0usize;
-        let mut frame_count = 0u32;
-        let start_time = std::time::Instant::now();
+    let snapshot = camera_state.snapshot();
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "state": snapshot.state,
+        "since": snapshot.since,
+        "state_ms": snapshot.state_ms,
+        "ready": snapshot.ready,
+        "last_error": snapshot.last_error,
+        "transitions": snapshot.transitions,
+        "camera": details,
+        "preview": watchdog.health(),
+    }))
+}
 
-        use tokio::io::AsyncReadExt;
+#[get("/camera/events")]
+pub async fn camera_events(events: web::Data<CameraEvents>) -> impl Responder {
+    let mut receiver = events.subscribe();
 
+    let stream = async_stream::stream! {
         loop {
-            let mut chunk = vec![0u8; 65536]; // 64KB chunks
-            match reader.read(&mut chunk).await {
-                Ok(0) => {
-                    warn!("Preview stream ended");
-                    break;
-                }
-                Ok(n) => {
-                    total_bytes += n;
-                    if total_bytes < 1000 {
-                        debug!("Read {} bytes from stream (total: {})", n, total_bytes);
-                    }
-                    buffer.extend_from_slice(&chunk[..n]);
-
-                    // Look for JPEG markers
-                    let mut i = 0;
-                    while i < buffer.len() {
-                        if !in_jpeg {
-                            // Look for JPEG start
-                            if i + 1 < buffer.len() && buffer[i] == JPEG_START[0] && buffer[i+1] == JPEG_START[1] {
-                                in_jpeg = true;
-                                jpeg_buffer.clear();
-                                jpeg_buffer.push(buffer[i]);
-                                jpeg_buffer.push(buffer[i+1]);
-                                i += 2;
-                            } else {
-                                i += 1;
-                            }
-                        } else {
-                            // Look for JPEG end
-                            if i + 1 < buffer.len() && buffer[i] == JPEG_END[0] && buffer[i+1] == JPEG_END[1] {
-                                jpeg_buffer.push(buffer[i]);
-                                jpeg_buffer.push(buffer[i+1]);
-
-                                // We have a complete JPEG frame
-                                frame_count += 1;
-                                if frame_count % 30 == 1 {  // Log every 30th frame
-                                    let elapsed = start_time.elapsed();
-                                    info!("Streaming: {} frames, {} bytes, {:.1} FPS",
-                                         frame_count, total_bytes,
-                                         frame_count as f32 / elapsed.as_secs_f32());
-                                }
-
-                                let boundary_prefix = format!("--{}\r\n", BOUNDARY).into_bytes();
-                                let header = b"Content-Type: image/jpeg\r\n\r\n";
-                                let tail = b"\r\n";
-
-                                let mut part = Vec::with_capacity(
-                                    boundary_prefix.len() + header.len() + jpeg_buffer.len() + tail.len()
-                                );
-                                part.extend_from_slice(&boundary_prefix);
-                                part.extend_from_slice(header);
-                                part.extend_from_slice(&jpeg_buffer);
-                                part.extend_from_slice(tail);
-
-                                yield Ok::<Bytes, actix_web::Error>(Bytes::from(part));
-
-                                in_jpeg = false;
-                                i += 2;
-                            } else {
-                                jpeg_buffer.push(buffer[i]);
-                                i += 1;
-                            }
-                        }
-                    }
-
-                    // Keep unprocessed bytes
-                    if in_jpeg {
-                        buffer.clear();
-                    } else {
-                        buffer.drain(..i);
-                    }
-                }
-                Err(e) => {
-                    error!("Error reading preview stream: {}", e);
-                    error!("Read {} bytes total before error", total_bytes);
-                    break;
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("Camera event client lagging, skipped {} event(s)", skipped);
+                    continue;
                 }
-            }
-        }
+                Err(RecvError::Closed) => break,
+            };
 
-        let _ = process.kill().await;
+            let json = serde_json::to_string(&event).unwrap_or_default();
+            yield Ok::<Bytes, actix_web::Error>(Bytes::from(format!("data: {}\n\n", json)));
+        }
     };
 
     HttpResponse::Ok()
-        .insert_header(("Content-Type", "multipart/x-mixed-replace; boundary=frame"))
+        .insert_header(("Content-Type", "text/event-stream"))
+        .insert_header(("Cache-Control", "no-cache"))
         .streaming(stream)
 }
 
 #[post("/capture")]
 pub async fn capture_image(
     config: web::Data<Config>,
-    _db_pool: web::Data<SqlitePool>,
+    db_pool: web::Data<SqlitePool>,
     body: Option<web::Json<serde_json::Value>>,
-    gphoto_camera: web::Data<Arc<Mutex<Option<Arc<crate::gphoto_camera::GPhotoCamera>>>>>,
+    camera: web::Data<SharedCamera>,
+    preview: web::Data<PreviewBroadcaster>,
 ) -> impl Responder {
     let capture_start = std::time::Instant::now();
     info!("=== CAPTURE IMAGE STARTED ===");
@@ -200,173 +270,413 @@ pub async fn capture_image(
 
     info!("Capture request with session_id: {:?}", session_id);
 
-    // Use GPhoto2 for high-resolution capture
-    info!("Using GPhoto2 for high-resolution capture");
+    // Use the shared camera instance
+    let camera_opt = camera.lock().unwrap().clone();
+    info!("Camera available: {}", camera_opt.is_some());
+    info!("Time since request start: {:?}", capture_start.elapsed());
 
-    let timestamp = chrono::Utc::now().timestamp();
-    let filename = config
-        .storage
-        .base_path
-        .join(format!("cap_{}.jpg", timestamp));
+    let Some(camera) = camera_opt else {
+        error!("Camera not available - camera not initialized");
+        return capture_error_response(CameraError::DeviceNotFound {
+            device: "camera not initialized".to_string(),
+        });
+    };
 
-    info!("Will save captured photo to: {:?}", filename);
+    info!("Starting photo capture via {}...", camera.type_name());
+    let camera_start = std::time::Instant::now();
+    let captured = capture_shots(
+        camera.as_ref(),
+        &config.storage.base_path,
+        1,
+        Duration::ZERO,
+        &config.camera,
+        Some(preview.get_ref()),
+    )
+    .await;
+    // Bring live view back whether or not the shot worked
+    spawn_preview_restart(camera);
+
+    let shot = match captured {
+        Ok(mut files) => files.remove(0),
+        Err(e) => {
+            error!("=== CAPTURE IMAGE FAILED ===");
+            error!(
+                "Capture failed after {:?}: {} ({}), total request time: {:?}",
+                camera_start.elapsed(),
+                e,
+                e.kind(),
+                capture_start.elapsed()
+            );
+            return capture_error_response(e);
+        }
+    };
 
-    let save_path = filename.clone();
+    let file_path = format!("/images/{}", shot.file_name);
+    info!("Photo capture successful!");
+    info!("  - Filename: {}", shot.file_name);
+    info!("  - Web path: {}", file_path);
+    info!("  - Capture took: {:?}", camera_start.elapsed());
+
+    let mut response_json = serde_json::json!({
+        "ok": true,
+        "path": file_path,
+        "file": shot.file_name,
+        "redirect": format!("/photo?file={}", shot.file_name),
+        "fallback_quality": shot.fallback,
+    });
+
+    // Update session if session_id was provided
+    if let Some(session_id) = session_id {
+        // A single capture replaces any earlier shots (e.g. a burst before a retake)
+        if let Err(e) =
+            SessionPhoto::replace_all(&session_id, std::slice::from_ref(&shot), &db_pool).await
+        {
+            warn!("Failed to attach photo to session {}: {}", session_id, e);
+        }
 
-    // Use the shared GPhoto2 camera instance
-    let camera_opt = gphoto_camera.lock().unwrap().clone();
-    info!("GPhoto camera available: {}", camera_opt.is_some());
-    info!("Time since request start: {:?}", capture_start.elapsed());
+        // Don't save the raw photo path - we'll save the templated version later
+        response_json["session_id"] = serde_json::json!(&session_id);
+        info!(
+            "Session {} will be updated with templated photo later",
+            session_id
+        );
+    }
 
-    let capture_result = if let Some(camera) = camera_opt.clone() {
-        info!("Starting photo capture via GPhoto2...");
-        let gphoto_start = std::time::Instant::now();
-        match camera.capture_photo(save_path.to_str().unwrap_or("")).await {
-            Ok(jpeg_data) => {
-                info!(
-                    "Photo captured successfully, size: {} bytes, capture took: {:?}",
-                    jpeg_data.len(),
-                    gphoto_start.elapsed()
-                );
-                // Save the JPEG directly
-                let save_path_log = save_path.clone();
-                let save_start = std::time::Instant::now();
-                let res = tokio::task::spawn_blocking(move || -> Result<(), String> {
-                    info!("Saving JPEG to disk: {:?}", save_path);
-                    std::fs::write(&save_path, &jpeg_data)
-                        .map_err(|e| format!("save JPEG: {e}"))?;
-                    info!("JPEG saved successfully");
-                    Ok(())
-                });
-                info!(
-                    "JPEG save task spawned for: {:?}, save task spawn took: {:?}",
-                    save_path_log,
-                    save_start.elapsed()
-                );
-
-                // Restart the preview stream after capture
-                info!("Restarting preview stream after capture");
-                let preview_restart_start = std::time::Instant::now();
-
-                // Start preview in background (simplified - no frame buffer needed)
-                let camera_clone = camera.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = camera_clone.start_preview_stream().await {
-                        warn!("Failed to restart preview stream: {}", e);
-                    } else {
-                        info!("Preview stream restarted successfully");
-                    }
-                });
-                info!(
-                    "Preview restart task spawned in: {:?}",
-                    preview_restart_start.elapsed()
-                );
-
-                Some((res, filename))
-            }
-            Err(e) => {
-                error!(
-                    "GPhoto2 capture failed after {:?}: {}",
-                    gphoto_start.elapsed(),
-                    e
-                );
-                error!("Total time since request: {:?}", capture_start.elapsed());
-
-                // Try to restart preview even after failure
-                info!("Attempting to restart preview stream after failed capture");
-
-                let camera_clone = camera.clone();
-                tokio::spawn(async move {
-                    info!("Starting preview restart after failure...");
-                    let restart_time = std::time::Instant::now();
-                    if let Err(e) = camera_clone.start_preview_stream().await {
-                        warn!(
-                            "Failed to restart preview stream after {:?}: {}",
-                            restart_time.elapsed(),
-                            e
-                        );
-                    } else {
-                        info!(
-                            "Preview stream restarted successfully after failure in {:?}",
-                            restart_time.elapsed()
-                        );
-                    }
-                });
-
-                None
-            }
-        }
-    } else {
-        error!("GPhoto2 camera not available - camera not initialized");
-        None
+    info!("=== CAPTURE IMAGE COMPLETED SUCCESSFULLY ===");
+    info!("Total capture request time: {:?}", capture_start.elapsed());
+    HttpResponse::Ok().json(response_json)
+}
+
+/// Start a server-timed countdown; progress and the result arrive on /capture/schedule/events
+#[post("/capture/schedule")]
+pub async fn schedule_capture(
+    config: web::Data<Config>,
+    body: Option<web::Json<serde_json::Value>>,
+    scheduler: web::Data<CaptureScheduler>,
+) -> impl Responder {
+    let body = body.map(|b| b.into_inner()).unwrap_or_default();
+    let request = ScheduleRequest {
+        session_id: body
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        countdown_secs: body
+            .get("countdown_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(config.countdown.secs)
+            .min(MAX_COUNTDOWN_SECS),
+        shots: body
+            .get("shots")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as u32)
+            .unwrap_or(config.camera.burst.shots)
+            .clamp(1, MAX_BURST_SHOTS),
     };
 
-    // Handle the capture result
-    match capture_result {
-        Some((res, filename)) => {
-            let res = res.await;
-
-            match res {
-                Ok(Ok(())) => {
-                    let file_name = filename.file_name().unwrap().to_string_lossy();
-                    let file_path = format!("/images/{}", file_name);
-
-                    info!("Photo capture successful!");
-                    info!("  - Filename: {}", file_name);
-                    info!("  - Web path: {}", file_path);
-                    info!("  - Full path: {:?}", filename);
-
-                    // Update session if session_id was provided
-                    let mut response_json = serde_json::json!({
-                        "ok": true,
-                        "path": file_path.clone(),
-                        "file": file_name,
-                        "redirect": format!("/photo?file={}", file_name),
-                    });
-
-                    if let Some(session_id) = session_id {
-                        // Don't save the raw photo path - we'll save the templated version later
-                        response_json["session_id"] = serde_json::json!(&session_id);
-                        info!(
-                            "Session {} will be updated with templated photo later",
-                            session_id
-                        );
-                    }
-
-                    info!("=== CAPTURE IMAGE COMPLETED SUCCESSFULLY ===");
-                    info!("Total capture request time: {:?}", capture_start.elapsed());
-                    HttpResponse::Ok().json(response_json)
-                }
-                Ok(Err(e)) => {
-                    error!("Failed to save captured photo: {}", e);
-                    error!(
-                        "Total time before save failure: {:?}",
-                        capture_start.elapsed()
-                    );
-                    HttpResponse::InternalServerError()
-                        .json(serde_json::json!({ "ok": false, "error": e }))
-                }
-                Err(_e) => {
-                    error!("Task join error while saving photo");
-                    error!(
-                        "Total time before join error: {:?}",
-                        capture_start.elapsed()
-                    );
-                    HttpResponse::InternalServerError()
-                        .json(serde_json::json!({ "ok": false, "error": "join error" }))
+    match scheduler.schedule(&config, request) {
+        Ok(scheduled) => HttpResponse::Ok().json(serde_json::json!({
+            "ok": true,
+            "capture_id": scheduled.capture_id,
+            "target_at": scheduled.target_at,
+            "target_epoch_ms": scheduled.target_epoch_ms,
+            "countdown_secs": scheduled.countdown_secs,
+            "shots": scheduled.shots,
+        })),
+        Err(e) => {
+            warn!("Capture not scheduled: {}", e);
+            let mut response = match e {
+                ScheduleError::CameraUnavailable => HttpResponse::ServiceUnavailable(),
+                ScheduleError::AlreadyScheduled => HttpResponse::Conflict(),
+            };
+            response.json(serde_json::json!({
+                "ok": false,
+                "error": e.to_string()
+            }))
+        }
+    }
+}
+
+#[get("/capture/schedule/events")]
+pub async fn capture_schedule_events(scheduler: web::Data<CaptureScheduler>) -> impl Responder {
+    let mut receiver = scheduler.subscribe();
+
+    let stream = async_stream::stream! {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("Countdown client lagging, skipped {} event(s)", skipped);
+                    continue;
                 }
-            }
+                Err(RecvError::Closed) => break,
+            };
+
+            let json = serde_json::to_string(&event).unwrap_or_default();
+            yield Ok::<Bytes, actix_web::Error>(Bytes::from(format!("data: {}\n\n", json)));
         }
-        None => {
-            error!("=== CAPTURE IMAGE FAILED ===");
+    };
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "text/event-stream"))
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
+
+#[get("/capture/burst")]
+pub async fn burst_settings(config: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "shots": config.camera.burst.shots,
+        "interval_secs": config.camera.burst.interval_secs,
+    }))
+}
+
+#[post("/capture/burst")]
+pub async fn capture_burst(
+    config: web::Data<Config>,
+    db_pool: web::Data<SqlitePool>,
+    body: Option<web::Json<serde_json::Value>>,
+    camera: web::Data<SharedCamera>,
+    preview: web::Data<PreviewBroadcaster>,
+) -> impl Responder {
+    let burst_start = std::time::Instant::now();
+
+    let session_id = body
+        .as_ref()
+        .and_then(|b| b.get("session_id"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let shots = body
+        .as_ref()
+        .and_then(|b| b.get("shots"))
+        .and_then(|v| v.as_u64())
+        .map(|n| n as u32)
+        .unwrap_or(config.camera.burst.shots)
+        .clamp(1, MAX_BURST_SHOTS);
+    let interval = Duration::from_secs(config.camera.burst.interval_secs);
+
+    info!("=== BURST CAPTURE STARTED ===");
+    info!(
+        "Burst of {} shot(s), {:?} apart, session_id: {:?}",
+        shots, interval, session_id
+    );
+
+    std::fs::create_dir_all(&config.storage.base_path).ok();
+
+    let Some(camera) = camera.lock().unwrap().clone() else {
+        error!("Camera not available - camera not initialized");
+        return capture_error_response(CameraError::DeviceNotFound {
+            device: "camera not initialized".to_string(),
+        });
+    };
+
+    let files = match capture_shots(
+        camera.as_ref(),
+        &config.storage.base_path,
+        shots,
+        interval,
+        &config.camera,
+        Some(preview.get_ref()),
+    )
+    .await
+    {
+        Ok(files) => files,
+        Err(e) => {
             error!(
-                "No camera available, total request time: {:?}",
-                capture_start.elapsed()
+                "Burst failed after {:?}: {} ({})",
+                burst_start.elapsed(),
+                e,
+                e.kind()
             );
-            HttpResponse::InternalServerError().json(serde_json::json!({
+            spawn_preview_restart(camera);
+            return capture_error_response(e);
+        }
+    };
+
+    spawn_preview_restart(camera);
+
+    if let Some(session_id) = &session_id {
+        if let Err(e) = SessionPhoto::replace_all(session_id, &files, &db_pool).await {
+            warn!("Failed to attach burst to session {}: {}", session_id, e);
+        }
+    }
+
+    info!("=== BURST CAPTURE COMPLETED SUCCESSFULLY ===");
+    info!("Captured {:?} in {:?}", files, burst_start.elapsed());
+
+    // `file` is the first shot so the single-photo flow keeps working unchanged
+    let first = files[0].file_name.clone();
+    let mut response_json = serde_json::json!({
+        "ok": true,
+        "shots": shots,
+        "fallback_quality": files.iter().any(|shot| shot.fallback),
+        "files": files,
+        "file": first,
+        "path": format!("/images/{}", first),
+        "redirect": format!("/photo?file={}", first),
+    });
+    if let Some(session_id) = session_id {
+        response_json["session_id"] = serde_json::json!(session_id);
+    }
+    HttpResponse::Ok().json(response_json)
+}
+
+/// Record a couple of seconds of live view and render it into the poster as an animated
+/// GIF. `{"boomerang": true}` plays it forward then back
+#[post("/capture/gif")]
+pub async fn capture_gif(
+    config: web::Data<Config>,
+    db_pool: web::Data<SqlitePool>,
+    body: Option<web::Json<serde_json::Value>>,
+    preview: web::Data<PreviewBroadcaster>,
+    fonts: web::Data<FontRegistry>,
+) -> impl Responder {
+    let gif_start = std::time::Instant::now();
+    let gif = &config.gif;
+
+    let session_id = body
+        .as_ref()
+        .and_then(|b| b.get("session_id"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let play_boomerang = body
+        .as_ref()
+        .and_then(|b| b.get("boomerang"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(gif.boomerang);
+
+    info!(
+        "Recording {}ms of live view at {} fps (boomerang: {}), session_id: {:?}",
+        gif.duration_ms, gif.fps, play_boomerang, session_id
+    );
+
+    let frames = match preview
+        .record(Duration::from_millis(gif.duration_ms), gif.fps)
+        .await
+    {
+        Ok(frames) => frames,
+        Err(e) => {
+            warn!("GIF recording failed: {}", e);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
                 "ok": false,
-                "error": "camera capture failed"
+                "error": e
+            }));
+        }
+    };
+
+    // Same poster text as the print, so the digital copy matches it
+    let mut session = None;
+    if let Some(session_id) = &session_id {
+        match Session::load(session_id, &db_pool).await {
+            Ok(loaded) => session = loaded,
+            Err(e) => warn!("Failed to load session {} for GIF: {}", session_id, e),
+        }
+    }
+    let mut template = PrintTemplate::new(
+        session
+            .as_ref()
+            .and_then(|s| s.story_text.as_deref())
+            .unwrap_or(&config.template.story_placeholder),
+    )
+    .with_layout(
+        config
+            .template_layout(session.as_ref().map_or(config.template.print_format, |s| {
+                s.print_format(&config.template)
             }))
+            .with_effects_for(
+                session.as_ref().and_then(|s| s.class),
+                session.as_ref().map_or(0, |s| s.effect_seed()),
+            ),
+    )
+    .with_fit(
+        session
+            .as_ref()
+            .map_or(config.template.photo_fit, |s| s.photo_fit(&config.template)),
+    )
+    .with_fonts(fonts.into_inner());
+    if let Some(group_name) = session.as_ref().and_then(|s| s.group_name.as_deref()) {
+        template = template.add_group_name(group_name);
+    }
+    if let Some(headline) = session.as_ref().and_then(|s| s.headline.as_deref()) {
+        template = template.add_headline(headline);
+    }
+
+    std::fs::create_dir_all(&config.storage.base_path).ok();
+    let file_name = format!("gif_{}.gif", chrono::Utc::now().timestamp());
+    let output = config.storage.base_path.join(&file_name);
+    let frame_delay_ms = 1000 / gif.fps.max(1);
+    let width = gif.width;
+    let orientation = config.camera.orientation;
+
+    let rendered = tokio::task::spawn_blocking(move || {
+        let mut images: Vec<image::DynamicImage> = frames
+            .iter()
+            .filter_map(|frame| {
+                image::load_from_memory_with_format(frame, image::ImageFormat::Jpeg).ok()
+            })
+            // Turned and mirrored like the captures, so it matches the print
+            .map(|frame| apply_mounting(frame, orientation.rotation, orientation.mirror_capture))
+            .collect();
+        if play_boomerang {
+            images = boomerang(images);
+        }
+        let count = images.len();
+        template
+            .apply_to_animation(images, frame_delay_ms, width, output.to_str().unwrap_or(""))
+            .map(|_| count)
+    })
+    .await;
+
+    let frame_count = match rendered {
+        Ok(Ok(count)) => count,
+        Ok(Err(e)) => {
+            error!("Failed to render GIF: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "ok": false,
+                "error": e.to_string()
+            }));
         }
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "ok": false, "error": "join error" }))
+        }
+    };
+
+    if let Some(mut session) = session {
+        session.gif_path = Some(file_name.clone());
+        if let Err(e) = session.update(&db_pool).await {
+            warn!("Failed to store GIF on session {}: {}", session.id, e);
+        }
+    }
+
+    info!(
+        "GIF {} rendered from {} frame(s) in {:?}",
+        file_name,
+        frame_count,
+        gif_start.elapsed()
+    );
+    let mut response_json = serde_json::json!({
+        "ok": true,
+        "file": file_name,
+        "path": format!("/images/{}", file_name),
+        "frames": frame_count,
+        "boomerang": play_boomerang,
+    });
+    if let Some(session_id) = session_id {
+        response_json["session_id"] = serde_json::json!(session_id);
     }
+    HttpResponse::Ok().json(response_json)
+}
+
+/// Failure response that tells the kiosk what went wrong so it can offer a retry
+fn capture_error_response(e: CameraError) -> HttpResponse {
+    let error_kind = e.kind();
+    let retryable = e.is_transient();
+    let app_error = AppError::Camera(e);
+    let mut response_json = app_error.error_response();
+    response_json["error_kind"] = serde_json::json!(error_kind);
+    response_json["retryable"] = serde_json::json!(retryable);
+    let status = actix_web::http::StatusCode::from_u16(app_error.status_code())
+        .unwrap_or(actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+    HttpResponse::build(status).json(response_json)
 }
@@ -0,0 +1,104 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use std::fmt::Write;
+
+use crate::camera_state::{CameraState, CameraStateMachine};
+use crate::fonts::FontRegistry;
+use crate::preview_broadcaster::PreviewBroadcaster;
+use crate::preview_watchdog::PreviewWatchdog;
+
+/// Prometheus text-format metrics for scraping or a quick look with curl
+#[get("/metrics")]
+pub async fn metrics(
+    camera_state: web::Data<CameraStateMachine>,
+    preview: web::Data<PreviewBroadcaster>,
+    watchdog: web::Data<PreviewWatchdog>,
+    fonts: web::Data<FontRegistry>,
+) -> impl Responder {
+    let health = watchdog.health();
+    let fonts = fonts.health();
+    let state = camera_state.current();
+    let mut out = String::new();
+
+    let mut metric = |name: &str, help: &str, kind: &str, value: f64| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+
+    metric(
+        "photobooth_camera_previewing",
+        "Whether live view is on",
+        "gauge",
+        (state == CameraState::Previewing) as u8 as f64,
+    );
+    metric(
+        "photobooth_preview_clients",
+        "Connected /preview clients, including the watchdog",
+        "gauge",
+        preview.client_count() as f64,
+    );
+    metric(
+        "photobooth_preview_fps",
+        "Frames per second arriving from the loopback device",
+        "gauge",
+        health.fps as f64,
+    );
+    metric(
+        "photobooth_preview_stalled",
+        "Whether live view is on but no frames are arriving",
+        "gauge",
+        health.stalled as u8 as f64,
+    );
+    metric(
+        "photobooth_preview_frames_total",
+        "Frames read from the loopback device",
+        "counter",
+        health.frames_total as f64,
+    );
+    metric(
+        "photobooth_preview_restarts_total",
+        "Live view restarts by the frozen-preview watchdog",
+        "counter",
+        health.restarts as f64,
+    );
+    metric(
+        "photobooth_preview_failed_restarts_total",
+        "Watchdog restarts where live view failed to start again",
+        "counter",
+        health.failed_restarts as f64,
+    );
+    metric(
+        "photobooth_fonts_missing",
+        "Layout fonts not found in FONT_DIRS, printed in the fallback font",
+        "gauge",
+        fonts.missing.len() as f64,
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(out)
+}
+
+/// One place to check that the booth can shoot and print as designed
+#[get("/health")]
+pub async fn health_status(
+    camera_state: web::Data<CameraStateMachine>,
+    watchdog: web::Data<PreviewWatchdog>,
+    fonts: web::Data<FontRegistry>,
+) -> impl Responder {
+    let snapshot = camera_state.snapshot();
+    let preview = watchdog.health();
+    let fonts = fonts.health();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "healthy": snapshot.ready && !preview.stalled && fonts.missing.is_empty(),
+        "camera": {
+            "state": snapshot.state,
+            "ready": snapshot.ready,
+            "last_error": snapshot.last_error,
+        },
+        "preview": preview,
+        "fonts": fonts,
+    }))
+}
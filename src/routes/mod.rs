@@ -1,14 +1,18 @@
 // Route modules organization
 
+pub mod admin_routes;
 pub mod base_routes;
 pub mod camera_routes;
+pub mod metrics_routes;
 pub mod printer_routes;
 pub mod selection_routes;
 pub mod session_routes;
 
 // Re-export all routes for convenience
+pub use admin_routes::*;
 pub use base_routes::*;
 pub use camera_routes::*;
+pub use metrics_routes::*;
 pub use printer_routes::*;
 pub use selection_routes::*;
 pub use session_routes::*;
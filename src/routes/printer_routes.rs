@@ -1,13 +1,15 @@
 use actix_web::{post, web, HttpResponse, Responder};
 use serde_json;
 use sqlx::SqlitePool;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
 use crate::config::Config;
-use crate::printers::{PaperSize, PrintJob, PrintQuality, Printer};
-use crate::session::Session;
-use crate::templates;
+use crate::fonts::FontRegistry;
+use crate::printers::{PrintJob, PrintQuality, Printer};
+use crate::session::{Session, SessionPhoto};
+use crate::templates::{self, PrintTemplate};
 
 #[post("/print")]
 pub async fn print_photo(
@@ -15,6 +17,7 @@ pub async fn print_photo(
     body: web::Json<serde_json::Value>,
     config: web::Data<Config>,
     db_pool: web::Data<SqlitePool>,
+    fonts: web::Data<FontRegistry>,
 ) -> impl Responder {
     info!("=== PRINT PHOTO STARTED ===");
     info!("Print photo request received with body: {:?}", body);
@@ -82,8 +85,14 @@ pub async fn print_photo(
     let mut group_name = String::new();
     let mut headline = String::new();
     let mut session_to_update = None;
+    let mut photo_paths = vec![file_path.clone()];
+    let mut fit = config.template.photo_fit;
+    let mut format = config.template.print_format;
+    // Character class and seed picking the photo effects
+    let mut effects_for = (None, 0);
 
     if let Some(session_id) = body.get("session_id").and_then(|v| v.as_str()) {
+        photo_paths = session_photo_paths(session_id, filename, &config, &db_pool).await;
         info!("Loading session {} for print job", session_id);
         info!("Session ID provided: {}", session_id);
         match Session::load(session_id, &db_pool).await {
@@ -132,6 +141,9 @@ pub async fn print_photo(
                     copies = session.copies_printed as u32;
                     info!("Using copies from session: {}", copies);
                 }
+                fit = session.photo_fit(&config.template);
+                format = session.print_format(&config.template);
+                effects_for = (session.class, session.effect_seed());
                 // Store session for later update with templated path
                 session_to_update = Some(session);
             }
@@ -152,16 +164,17 @@ pub async fn print_photo(
     let templated_filename = config.storage.base_path.join(&templated_filename_only);
 
     info!("=== FILE MANAGEMENT - CREATING TEMPLATE ===");
-    info!("  Raw photo (INPUT): {:?}", file_path);
+    info!("  Raw photos (INPUT): {:?}", photo_paths);
     info!("  Template (OUTPUT): {:?}", templated_filename);
     info!("  Template will be KEPT for display");
-    info!("  Raw photo will be DELETED after 30 seconds");
+    info!("  Raw photos will be DELETED after 30 seconds");
 
     info!("=== CREATING TEMPLATED PRINT ===");
     info!("  Timestamp: {}", timestamp);
-    info!("  Raw photo source: {:?}", file_path);
+    info!("  Raw photo sources: {:?}", photo_paths);
     info!("  Template destination: {:?}", templated_filename);
     info!("  Background image: {:?}", config.background_path());
+    info!("  Print format: {}", format);
     info!("  Group name: '{}'", group_name);
     info!("  Headline: '{}'", headline);
     info!(
@@ -174,14 +187,18 @@ pub async fn print_photo(
         story_text.len()
     );
 
-    match templates::create_templated_print_with_background(
-        file_path.to_str().unwrap(),
-        templated_filename.to_str().unwrap(),
-        &story_text,
-        &group_name,
-        &headline,
-        config.background_path().to_str().unwrap(),
-    ) {
+    let photo_strs: Vec<&str> = photo_paths.iter().map(|p| p.to_str().unwrap()).collect();
+    let template = PrintTemplate::new(&story_text)
+        .with_layout(
+            config
+                .template_layout(format)
+                .with_effects_for(effects_for.0, effects_for.1),
+        )
+        .add_group_name(&group_name)
+        .add_headline(&headline)
+        .with_fit(fit)
+        .with_fonts(fonts.into_inner());
+    match template.apply_to_photos(&photo_strs, templated_filename.to_str().unwrap()) {
         Ok(_) => {
             info!("=== TEMPLATE CREATED SUCCESSFULLY ===");
             info!("  Template filename: {}", templated_filename_only);
@@ -226,12 +243,31 @@ pub async fn print_photo(
                 warn!("No session to update with templated path");
             }
 
-            // Use the templated file for printing
+            // Lay the print out on the sheet the format uses; 2-up sheets carry two copies
+            let sheet_filename = config
+                .storage
+                .base_path
+                .join(format!("sheet_{}.png", timestamp));
+            if let Err(e) = templates::impose_for_print(
+                templated_filename.to_str().unwrap(),
+                sheet_filename.to_str().unwrap(),
+                format,
+            ) {
+                error!("Failed to lay out {} sheet: {}", format, e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "ok": false,
+                    "error": format!("Failed to lay out print sheet: {}", e)
+                }));
+            }
+            let sheets = copies.div_ceil(format.copies_per_sheet());
+
+            // Use the sheet for printing
             let print_job = PrintJob {
-                file_path: templated_filename.to_str().unwrap().to_string(),
-                copies: copies,
-                paper_size: PaperSize::Photo4x6,
+                file_path: sheet_filename.to_str().unwrap().to_string(),
+                copies: sheets,
+                paper_size: format.paper_size(),
                 quality: PrintQuality::Draft,
+                cut_in_half: format.cut_in_half(),
             };
 
             info!("Sending print job to printer: {:?}", print_job);
@@ -240,40 +276,45 @@ pub async fn print_photo(
                     info!("Print job submitted successfully with ID: {}", job_id);
                     info!("=== FILE MANAGEMENT - POST-PRINT CLEANUP SCHEDULED ===");
                     info!("  Template file (KEEPING): {:?}", templated_filename);
-                    info!("  Raw file (DELETING in 30s): {:?}", file_path);
-                    // Clean up raw capture file after sending to printer (keep the templated version)
-                    let raw_file_to_delete = file_path.clone();
+                    info!("  Raw files (DELETING in 30s): {:?}", photo_paths);
+                    // Clean up raw capture files after sending to printer (keep the templated version)
+                    let raw_files_to_delete = photo_paths.clone();
                     tokio::task::spawn(async move {
                         tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
 
                         info!("=== FILE MANAGEMENT - CLEANUP TIMER EXPIRED ===");
-                        // Safety check: only delete raw capture files (cap_*.jpg)
-                        if let Some(file_name) = raw_file_to_delete.file_name() {
-                            let name_str = file_name.to_string_lossy();
-                            if name_str.starts_with("cap_") && name_str.ends_with(".jpg") {
-                                info!("Deleting raw capture file: {:?}", raw_file_to_delete);
-                                match std::fs::remove_file(&raw_file_to_delete) {
-                                    Ok(_) => info!(
-                                        "Successfully deleted raw capture: {:?}",
+                        for raw_file_to_delete in raw_files_to_delete {
+                            // Safety check: only delete raw capture files (cap_*.jpg)
+                            if let Some(file_name) = raw_file_to_delete.file_name() {
+                                let name_str = file_name.to_string_lossy();
+                                if name_str.starts_with("cap_") && name_str.ends_with(".jpg") {
+                                    info!("Deleting raw capture file: {:?}", raw_file_to_delete);
+                                    match std::fs::remove_file(&raw_file_to_delete) {
+                                        Ok(_) => info!(
+                                            "Successfully deleted raw capture: {:?}",
+                                            raw_file_to_delete
+                                        ),
+                                        Err(e) => warn!(
+                                            "Failed to delete raw capture: {:?}, error: {}",
+                                            raw_file_to_delete, e
+                                        ),
+                                    }
+                                } else {
+                                    warn!(
+                                        "SAFETY CHECK: Refusing to delete non-capture file: {:?}",
                                         raw_file_to_delete
-                                    ),
-                                    Err(e) => warn!(
-                                        "Failed to delete raw capture: {:?}, error: {}",
-                                        raw_file_to_delete, e
-                                    ),
+                                    );
+                                    warn!(
+                                        "Only cap_*.jpg files should be deleted, not: {}",
+                                        name_str
+                                    );
                                 }
                             } else {
                                 warn!(
-                                    "SAFETY CHECK: Refusing to delete non-capture file: {:?}",
+                                    "Could not determine filename for cleanup: {:?}",
                                     raw_file_to_delete
                                 );
-                                warn!("Only cap_*.jpg files should be deleted, not: {}", name_str);
                             }
-                        } else {
-                            warn!(
-                                "Could not determine filename for cleanup: {:?}",
-                                raw_file_to_delete
-                            );
                         }
                     });
 
@@ -314,6 +355,7 @@ pub async fn preview_print(
     body: web::Json<serde_json::Value>,
     config: web::Data<Config>,
     db_pool: web::Data<SqlitePool>,
+    fonts: web::Data<FontRegistry>,
 ) -> impl Responder {
     let filename = match body.get("filename").and_then(|v| v.as_str()) {
         Some(f) => f,
@@ -341,10 +383,17 @@ pub async fn preview_print(
     let mut story_text = config.template.story_placeholder.clone();
     let mut group_name = String::new();
     let mut headline = String::new();
+    let mut fit = config.template.photo_fit;
+    let mut format = config.template.print_format;
+    // Character class and seed picking the photo effects
+    let mut effects_for = (None, 0);
 
     if let Some(session_id) = body.get("session_id").and_then(|v| v.as_str()) {
         match Session::load(session_id, &db_pool).await {
             Ok(Some(session)) => {
+                fit = session.photo_fit(&config.template);
+                format = session.print_format(&config.template);
+                effects_for = (session.class, session.effect_seed());
                 // Use session's story text if available
                 if let Some(story) = &session.story_text {
                     story_text = story.clone();
@@ -371,14 +420,23 @@ pub async fn preview_print(
     let preview_filename = format!("preview_{}.png", chrono::Utc::now().timestamp());
     let preview_path = config.storage.base_path.join(&preview_filename);
 
-    match templates::create_templated_print_with_background(
-        file_path.to_str().unwrap(),
-        preview_path.to_str().unwrap(),
-        &story_text,
-        &group_name,
-        &headline,
-        config.background_path().to_str().unwrap(),
-    ) {
+    let photo_paths = match body.get("session_id").and_then(|v| v.as_str()) {
+        Some(session_id) => session_photo_paths(session_id, filename, &config, &db_pool).await,
+        None => vec![file_path.clone()],
+    };
+    let photo_strs: Vec<&str> = photo_paths.iter().map(|p| p.to_str().unwrap()).collect();
+
+    let template = PrintTemplate::new(&story_text)
+        .with_layout(
+            config
+                .template_layout(format)
+                .with_effects_for(effects_for.0, effects_for.1),
+        )
+        .add_group_name(&group_name)
+        .add_headline(&headline)
+        .with_fit(fit)
+        .with_fonts(fonts.into_inner());
+    match template.apply_to_photos(&photo_strs, preview_path.to_str().unwrap()) {
         Ok(()) => {
             // Update session with templated preview path if we have a session
             if let Some(session_id_str) = body.get("session_id").and_then(|v| v.as_str()) {
@@ -419,3 +477,31 @@ pub async fn preview_print(
         })),
     }
 }
+
+/// Raw shots to render for a print: every shot of the session's burst when `filename`
+/// belongs to it, otherwise just `filename`
+async fn session_photo_paths(
+    session_id: &str,
+    filename: &str,
+    config: &Config,
+    db_pool: &SqlitePool,
+) -> Vec<PathBuf> {
+    let requested = vec![config.storage.base_path.join(filename)];
+
+    match SessionPhoto::for_session(session_id, db_pool).await {
+        Ok(photos) if photos.iter().any(|p| p.file_name == filename) => {
+            let paths: Vec<PathBuf> = photos
+                .iter()
+                .map(|p| config.storage.base_path.join(&p.file_name))
+                .filter(|p| p.exists())
+                .collect();
+            info!("Using {} shot(s) from session {}", paths.len(), session_id);
+            paths
+        }
+        Ok(_) => requested,
+        Err(e) => {
+            warn!("Failed to load photos for session {}: {}", session_id, e);
+            requested
+        }
+    }
+}
@@ -4,8 +4,10 @@ use sqlx::SqlitePool;
 use tracing::{info, warn};
 
 use crate::config::Config;
-use crate::session::Session;
-use crate::templates::create_templated_print_with_background;
+use crate::fonts::FontRegistry;
+use crate::print_format::PrintFormat;
+use crate::session::{Session, SessionPhoto};
+use crate::templates::PrintTemplate;
 
 #[post("/session")]
 pub async fn create_session(db_pool: web::Data<SqlitePool>) -> impl Responder {
@@ -87,6 +89,17 @@ pub async fn update_session(
             if let Some(mailing_list) = body.get("mailing_list").and_then(|v| v.as_i64()) {
                 session.mailing_list = mailing_list as i32;
             }
+            if let Some(format) = body.get("print_format").and_then(|v| v.as_str()) {
+                match format.parse::<PrintFormat>() {
+                    Ok(format) => session.print_format = Some(format.id().to_string()),
+                    Err(e) => {
+                        return HttpResponse::BadRequest().json(serde_json::json!({
+                            "ok": false,
+                            "error": e
+                        }));
+                    }
+                }
+            }
 
             // Save updated session
             match session.update(&db_pool).await {
@@ -111,6 +124,76 @@ pub async fn update_session(
     }
 }
 
+/// Re-frame a session's shots before printing: `{"fit": "cover" | "contain"}` or
+/// `{"fit": "focal", "x": 0.3, "y": 0.4}` to crop around a point (fractions of the photo).
+/// `{"fit": null}` goes back to the configured fit
+#[post("/session/{id}/framing")]
+pub async fn set_framing(
+    path: web::Path<String>,
+    body: web::Json<serde_json::Value>,
+    db_pool: web::Data<SqlitePool>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let session_id = path.into_inner();
+    let fit = body.get("fit").and_then(|v| v.as_str());
+    let focal = (
+        body.get("x").and_then(|v| v.as_f64()),
+        body.get("y").and_then(|v| v.as_f64()),
+    );
+
+    let focal = match (fit, focal) {
+        (Some("focal"), (Some(x), Some(y)))
+            if (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y) =>
+        {
+            Some((x, y))
+        }
+        (Some("focal"), _) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "ok": false,
+                "error": "focal framing needs x and y between 0 and 1"
+            }));
+        }
+        (Some("cover") | Some("contain") | None, _) => None,
+        (Some(other), _) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "ok": false,
+                "error": format!("Unknown fit '{}'", other)
+            }));
+        }
+    };
+
+    match Session::load(&session_id, &db_pool).await {
+        Ok(Some(mut session)) => {
+            session.photo_fit = fit.map(str::to_string);
+            session.focal_x = focal.map(|(x, _)| x);
+            session.focal_y = focal.map(|(_, y)| y);
+
+            match session.update(&db_pool).await {
+                Ok(()) => {
+                    info!("Session {} re-framed: {:?}", session_id, fit);
+                    HttpResponse::Ok().json(serde_json::json!({
+                        "ok": true,
+                        "fit": session.photo_fit(&config.template),
+                        "session": session
+                    }))
+                }
+                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                    "ok": false,
+                    "error": format!("Failed to update session: {}", e)
+                })),
+            }
+        }
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "ok": false,
+            "error": "Session not found"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "ok": false,
+            "error": format!("Failed to load session: {}", e)
+        })),
+    }
+}
+
 #[post("/session/{id}/generate-story")]
 pub async fn generate_story(
     path: web::Path<String>,
@@ -152,6 +235,7 @@ pub async fn save_session_final(
     path: web::Path<String>,
     db_pool: web::Data<SqlitePool>,
     config: web::Data<Config>,
+    fonts: web::Data<FontRegistry>,
 ) -> impl Responder {
     let session_id = path.into_inner();
 
@@ -163,6 +247,20 @@ pub async fn save_session_final(
                 session.generate_story();
             }
 
+            // Shots recorded at capture time; older sessions fall back to scanning storage
+            let session_photos: Vec<std::path::PathBuf> =
+                match SessionPhoto::for_session(&session_id, &db_pool).await {
+                    Ok(photos) => photos
+                        .iter()
+                        .map(|p| config.storage.base_path.join(&p.file_name))
+                        .filter(|p| p.exists())
+                        .collect(),
+                    Err(e) => {
+                        warn!("Failed to load photos for session {}: {}", session_id, e);
+                        Vec::new()
+                    }
+                };
+
             // If we have a captured image but no templated photo_path, create the template
             let captured_image = session
                 .email
@@ -180,7 +278,12 @@ pub async fn save_session_final(
 
             // Create templated image if we have the captured image
             if session.photo_path.is_none() {
-                if let Some(captured_path) = captured_image {
+                let captured_paths = if session_photos.is_empty() {
+                    captured_image.into_iter().collect()
+                } else {
+                    session_photos
+                };
+                if !captured_paths.is_empty() {
                     let preview_filename = format!(
                         "preview_{}_{}.jpg",
                         session_id,
@@ -189,14 +292,22 @@ pub async fn save_session_final(
                     let preview_path = config.storage.base_path.join(&preview_filename);
 
                     // Create the templated image
-                    match create_templated_print_with_background(
-                        captured_path.to_str().unwrap_or(""),
-                        preview_path.to_str().unwrap_or(""),
-                        session.story_text.as_deref().unwrap_or(""),
-                        session.group_name.as_deref().unwrap_or(""),
-                        session.headline.as_deref().unwrap_or(""),
-                        config.background_path().to_str().unwrap_or(""),
-                    ) {
+                    let photo_strs: Vec<&str> = captured_paths
+                        .iter()
+                        .map(|p| p.to_str().unwrap_or(""))
+                        .collect();
+                    let template = PrintTemplate::new(session.story_text.as_deref().unwrap_or(""))
+                        .with_layout(
+                            config
+                                .template_layout(session.print_format(&config.template))
+                                .with_effects_for(session.class, session.effect_seed()),
+                        )
+                        .add_group_name(session.group_name.as_deref().unwrap_or(""))
+                        .add_headline(session.headline.as_deref().unwrap_or(""))
+                        .with_fit(session.photo_fit(&config.template))
+                        .with_fonts(fonts.into_inner());
+                    match template.apply_to_photos(&photo_strs, preview_path.to_str().unwrap_or(""))
+                    {
                         Ok(_) => {
                             info!("Created templated preview image: {}", preview_filename);
                             session.photo_path = Some(preview_filename);
@@ -4,7 +4,11 @@ use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, SqlitePool};
 use uuid::Uuid;
 
+use crate::camera::CapturedShot;
+use crate::config::TemplateConfig;
 use crate::errors::{AppResult, DatabaseError};
+use crate::print_format::PrintFormat;
+use crate::templates::PhotoFit;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Session {
@@ -19,6 +23,15 @@ pub struct Session {
     pub story_text: Option<String>,
     pub headline: Option<String>,
     pub mailing_list: i32,
+    /// Animated poster (GIF) recorded from live view, if the guests made one
+    pub gif_path: Option<String>,
+    /// Staff re-framing: cover, contain or focal. None uses the configured fit
+    pub photo_fit: Option<String>,
+    /// Crop anchor for the focal fit, as fractions of the photo's width and height
+    pub focal_x: Option<f64>,
+    pub focal_y: Option<f64>,
+    /// 4x6, 5x7, 2x6 or 4x6-2up. None prints the event's format
+    pub print_format: Option<String>,
 }
 
 impl Session {
@@ -35,6 +48,11 @@ impl Session {
             story_text: None,
             headline: None,
             mailing_list: 0,
+            gif_path: None,
+            photo_fit: None,
+            focal_x: None,
+            focal_y: None,
+            print_format: None,
         }
     }
 
@@ -43,9 +61,10 @@ impl Session {
             r#"
             INSERT INTO session (
                 id, group_name, created_at, class, choice,
-                email, photo_path, copies_printed, story_text, headline, mailing_list
+                email, photo_path, copies_printed, story_text, headline, mailing_list,
+                gif_path, photo_fit, focal_x, focal_y, print_format
             ) VALUES (
-                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16
             )
             "#,
         )
@@ -60,6 +79,11 @@ impl Session {
         .bind(&self.story_text)
         .bind(&self.headline)
         .bind(&self.mailing_list)
+        .bind(&self.gif_path)
+        .bind(&self.photo_fit)
+        .bind(self.focal_x)
+        .bind(self.focal_y)
+        .bind(&self.print_format)
         .execute(pool)
         .await
         .map_err(|e| DatabaseError::QueryFailed(format!("Failed to save session: {}", e)))?;
@@ -79,7 +103,12 @@ impl Session {
                 copies_printed = ?7,
                 story_text = ?8,
                 headline = ?9,
-                mailing_list = ?10
+                mailing_list = ?10,
+                gif_path = ?11,
+                photo_fit = ?12,
+                focal_x = ?13,
+                focal_y = ?14,
+                print_format = ?15
             WHERE id = ?1
             "#,
         )
@@ -93,6 +122,11 @@ impl Session {
         .bind(&self.story_text)
         .bind(&self.headline)
         .bind(&self.mailing_list)
+        .bind(&self.gif_path)
+        .bind(&self.photo_fit)
+        .bind(self.focal_x)
+        .bind(self.focal_y)
+        .bind(&self.print_format)
         .execute(pool)
         .await
         .map_err(|e| DatabaseError::QueryFailed(format!("Failed to update session: {}", e)))?;
@@ -105,7 +139,8 @@ impl Session {
             r#"
             SELECT
                 id, group_name, created_at, class, choice,
-                email, photo_path, copies_printed, story_text, headline, mailing_list
+                email, photo_path, copies_printed, story_text, headline, mailing_list,
+                gif_path, photo_fit, focal_x, focal_y, print_format
             FROM session
             WHERE id = ?1
             "#,
@@ -118,6 +153,29 @@ impl Session {
         Ok(session)
     }
 
+    /// How this session's shots fill the print, falling back to the configured fit
+    pub fn photo_fit(&self, template: &TemplateConfig) -> PhotoFit {
+        match self.photo_fit.as_deref() {
+            Some("cover") => PhotoFit::Cover,
+            Some("contain") => PhotoFit::Contain {
+                letterbox: template.letterbox_color,
+            },
+            Some("focal") => PhotoFit::Focal {
+                x: self.focal_x.unwrap_or(0.5) as f32,
+                y: self.focal_y.unwrap_or(0.5) as f32,
+            },
+            _ => template.photo_fit,
+        }
+    }
+
+    /// What this session prints on, falling back to the event's format
+    pub fn print_format(&self, template: &TemplateConfig) -> PrintFormat {
+        self.print_format
+            .as_deref()
+            .and_then(|f| f.parse().ok())
+            .unwrap_or(template.print_format)
+    }
+
     pub fn is_complete(&self) -> bool {
         self.group_name.is_some()
             && self.class.is_some()
@@ -128,6 +186,14 @@ impl Session {
             && self.headline.is_some()
     }
 
+    /// Seed for the photo effects: stable for the session, different between sessions
+    pub fn effect_seed(&self) -> u64 {
+        // FNV-1a, which unlike the std hasher is guaranteed not to change between builds
+        self.id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
     pub fn generate_story(&mut self) {
         if let (Some(class_idx), Some(choice_idx)) = (self.class, self.choice) {
             let lands = [
@@ -301,6 +367,82 @@ impl Session {
     }
 }
 
+/// One raw capture attached to a session, in shot order
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionPhoto {
+    pub id: i64,
+    pub session_id: String,
+    pub shot_index: i32,
+    pub file_name: String,
+    pub created_at: String,
+    /// Rebuilt from a live view frame because the full-resolution capture failed
+    pub fallback: bool,
+}
+
+impl SessionPhoto {
+    /// Attach `shots` to the session in order, replacing any earlier shots (retakes)
+    pub async fn replace_all(
+        session_id: &str,
+        shots: &[CapturedShot],
+        pool: &SqlitePool,
+    ) -> AppResult<()> {
+        let mut tx = pool.begin().await.map_err(|e| {
+            DatabaseError::TransactionFailed(format!("Failed to start transaction: {}", e))
+        })?;
+
+        sqlx::query("DELETE FROM session_photo WHERE session_id = ?1")
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                DatabaseError::QueryFailed(format!("Failed to clear session photos: {}", e))
+            })?;
+
+        let created_at = Utc::now().to_rfc3339();
+        for (index, shot) in shots.iter().enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO session_photo (session_id, shot_index, file_name, created_at, fallback)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+            )
+            .bind(session_id)
+            .bind(index as i32)
+            .bind(&shot.file_name)
+            .bind(&created_at)
+            .bind(shot.fallback)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                DatabaseError::QueryFailed(format!("Failed to save session photo: {}", e))
+            })?;
+        }
+
+        tx.commit().await.map_err(|e| {
+            DatabaseError::TransactionFailed(format!("Failed to commit session photos: {}", e))
+        })?;
+
+        Ok(())
+    }
+
+    pub async fn for_session(session_id: &str, pool: &SqlitePool) -> AppResult<Vec<Self>> {
+        let photos = sqlx::query_as::<_, SessionPhoto>(
+            r#"
+            SELECT id, session_id, shot_index, file_name, created_at, fallback
+            FROM session_photo
+            WHERE session_id = ?1
+            ORDER BY shot_index
+            "#,
+        )
+        .bind(session_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(format!("Failed to load session photos: {}", e)))?;
+
+        Ok(photos)
+    }
+}
+
 impl Default for Session {
     fn default() -> Self {
         Self::new()
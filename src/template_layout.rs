@@ -0,0 +1,831 @@
+// Declarative print layouts: canvas, background and overlay layers, photo slots and text
+// boxes, read from a TOML or JSON file so a designer can move things without a rebuild
+
+use image::Rgb;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+use crate::fonts::{self, FontStyle};
+use crate::photo_effects::PhotoEffect;
+use crate::print_format::{PrintFormat, PRINT_DPI};
+use crate::templates::{
+    parse_hex_color, PhotoFit, TemplateError, PHOTO_HEIGHT, PHOTO_WIDTH, SLOT_GAP,
+};
+
+// The built-in poster is drawn for a 4x6" print and scaled for larger ones
+const PRINT_WIDTH: u32 = 1200;
+const PHOTO_Y_POSITION: u32 = 300;
+// Built-in strip: shots stacked down a 2x6" print
+const STRIP_SHOTS: u32 = 4;
+const STRIP_MARGIN: u32 = 40;
+const STRIP_GAP: u32 = 20;
+const POSTER_FONT: &str = "IMFellEnglish";
+// Where the photos and the text sit in the layer stack
+pub const PHOTO_Z: i32 = 0;
+pub const TEXT_Z: i32 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateLayout {
+    /// The print this layout is drawn for; the canvas is scaled to it when printing
+    #[serde(default)]
+    pub format: PrintFormat,
+    pub canvas: Canvas,
+    /// Drawn under the photos, in order
+    #[serde(default)]
+    pub background: Vec<Layer>,
+    /// Drawn over the photos and under the text, in order
+    #[serde(default)]
+    pub overlays: Vec<Layer>,
+    /// Drawn wherever their `z` puts them, over the photos if it isn't set
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    pub photo_slots: Vec<PhotoSlot>,
+    #[serde(default)]
+    pub text: Vec<TextBox>,
+    /// Run on every photo, in order, before it is placed
+    #[serde(default)]
+    pub effects: Vec<PhotoEffect>,
+    /// Chains that replace `effects` for one character class, keyed by class number
+    #[serde(default)]
+    pub class_effects: HashMap<String, Vec<PhotoEffect>>,
+    /// Seeds the random parts of the effects; set per session by `with_effects_for`
+    #[serde(skip)]
+    pub effect_seed: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+    /// Shows wherever no layer covers the canvas
+    #[serde(default = "white", deserialize_with = "hex_color")]
+    pub background_color: Rgb<u8>,
+}
+
+/// An image layer; without a size it is stretched over the whole canvas
+#[derive(Debug, Clone, Deserialize)]
+pub struct Layer {
+    pub image: PathBuf,
+    #[serde(default)]
+    pub x: u32,
+    #[serde(default)]
+    pub y: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Stacking order: below 0 is under the photos, 0-99 over the photos and under the
+    /// text, 100 and up over the text. Defaults to just under or over the photos
+    pub z: Option<i32>,
+    /// Multiplies the image's own alpha, 0.0-1.0
+    #[serde(default = "opaque")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub blend: BlendMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    /// Darkens what is under it, so white disappears: ink stamps, stains, paper grain
+    Multiply,
+}
+
+/// One step in drawing a print, bottom to top
+#[derive(Debug, Clone, Copy)]
+pub enum StackItem<'a> {
+    Layer(&'a Layer),
+    Photos,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct PhotoSlot {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// How the photo fills this slot; unset uses the session's or the event's fit
+    pub fit: Option<PhotoFit>,
+    /// Degrees clockwise about the slot's centre; the turned photo's corners overhang it
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl PhotoSlot {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        PhotoSlot {
+            x,
+            y,
+            width,
+            height,
+            fit: None,
+            rotation: 0.0,
+        }
+    }
+}
+
+/// Built-in ways to share one photo area between several shots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collage {
+    /// Two by two
+    Grid,
+    /// Three shots stacked down the middle
+    Strip,
+    /// One large shot with three thumbnails beside it
+    Hero,
+}
+
+impl Collage {
+    /// Split `area` into this collage's slots, largest first
+    pub fn slots(&self, area: PhotoSlot) -> Vec<PhotoSlot> {
+        let PhotoSlot {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } = area;
+        // Cells keep the area's fit but not its tilt
+        let slot = |x, y, width, height| PhotoSlot {
+            x,
+            y,
+            width,
+            height,
+            rotation: 0.0,
+            ..area
+        };
+        match self {
+            Collage::Grid => {
+                let cell_width = width.saturating_sub(SLOT_GAP) / 2;
+                let cell_height = height.saturating_sub(SLOT_GAP) / 2;
+                (0..4)
+                    .map(|i| {
+                        slot(
+                            x + (i % 2) * (cell_width + SLOT_GAP),
+                            y + (i / 2) * (cell_height + SLOT_GAP),
+                            cell_width,
+                            cell_height,
+                        )
+                    })
+                    .collect()
+            }
+            Collage::Strip => {
+                let cell_height = height.saturating_sub(2 * SLOT_GAP) / 3;
+                let cell_width = (cell_height * 3 / 2).min(width);
+                let left = x + (width - cell_width) / 2;
+                (0..3)
+                    .map(|i| {
+                        slot(
+                            left,
+                            y + i * (cell_height + SLOT_GAP),
+                            cell_width,
+                            cell_height,
+                        )
+                    })
+                    .collect()
+            }
+            Collage::Hero => {
+                let hero_width = width.saturating_sub(SLOT_GAP) * 2 / 3;
+                let thumb_x = x + hero_width + SLOT_GAP;
+                let thumb_width = width.saturating_sub(hero_width + SLOT_GAP);
+                let thumb_height = height.saturating_sub(2 * SLOT_GAP) / 3;
+                std::iter::once(slot(x, y, hero_width, height))
+                    .chain((0..3).map(|i| {
+                        slot(
+                            thumb_x,
+                            y + i * (thumb_height + SLOT_GAP),
+                            thumb_width,
+                            thumb_height,
+                        )
+                    }))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Collage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "grid" => Ok(Collage::Grid),
+            "strip" => Ok(Collage::Strip),
+            "hero" => Ok(Collage::Hero),
+            other => Err(format!(
+                "Unknown collage '{}' (expected grid, strip or hero)",
+                other
+            )),
+        }
+    }
+}
+
+/// Session fields a text box can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextField {
+    GroupName,
+    Headline,
+    Story,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextBox {
+    /// Session field to show; boxes without one show `text` as is
+    pub field: Option<TextField>,
+    #[serde(default)]
+    pub text: String,
+    /// Font family from FONT_DIRS, e.g. "IMFellEnglish", or the path of a font file
+    pub font: String,
+    #[serde(default)]
+    pub style: FontStyle,
+    pub size: f32,
+    /// Smallest size the text shrinks to before it is cut short; defaults to 60% of `size`
+    pub min_size: Option<f32>,
+    #[serde(default = "dark_gray", deserialize_with = "hex_color")]
+    pub color: Rgb<u8>,
+    #[serde(default)]
+    pub align: TextAlign,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    /// Height of the box; text taller than this shrinks, then is cut off with an ellipsis
+    pub max_height: Option<u32>,
+    /// Wrap to `width` instead of drawing a single line
+    #[serde(default)]
+    pub wrap: bool,
+    /// Distance between lines at `size`; defaults to the font's own line spacing
+    pub line_height: Option<u32>,
+}
+
+// Text may shrink to this fraction of its size before it is cut short
+const DEFAULT_MIN_SIZE: f32 = 0.6;
+
+impl TextBox {
+    pub fn min_size(&self) -> f32 {
+        self.min_size.unwrap_or(self.size * DEFAULT_MIN_SIZE)
+    }
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+fn white() -> Rgb<u8> {
+    Rgb([255, 255, 255])
+}
+
+fn dark_gray() -> Rgb<u8> {
+    Rgb([50, 50, 50])
+}
+
+pub(crate) fn hex_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgb<u8>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_hex_color(&value)
+        .map(Rgb)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid colour '{}'", value)))
+}
+
+impl Default for TemplateLayout {
+    fn default() -> Self {
+        Self::for_format(PrintFormat::default())
+    }
+}
+
+fn text_line(field: TextField, style: FontStyle, size: f32, y: u32, width: u32) -> TextBox {
+    TextBox {
+        field: Some(field),
+        text: String::new(),
+        font: POSTER_FONT.to_string(),
+        style,
+        size,
+        min_size: None,
+        color: dark_gray(),
+        align: TextAlign::Center,
+        x: 0,
+        y,
+        width,
+        max_height: None,
+        wrap: false,
+        line_height: None,
+    }
+}
+
+/// The poster the booth has always printed: one 3:2 photo near the top, the group name
+/// and headline under it, then the story. Drawn for 4x6" and scaled up with the width
+fn poster_layout(format: PrintFormat) -> TemplateLayout {
+    let (width, height) = format.copy_size();
+    let scale = |v: u32| v * width / PRINT_WIDTH;
+    let font_size = |size: f32| size * width as f32 / PRINT_WIDTH as f32;
+
+    let photo_y = scale(PHOTO_Y_POSITION);
+    let below_photo = photo_y + scale(PHOTO_HEIGHT);
+    let story_y = below_photo + scale(350);
+
+    TemplateLayout {
+        format,
+        canvas: Canvas {
+            width,
+            height,
+            dpi: PRINT_DPI,
+            background_color: white(),
+        },
+        background: Vec::new(),
+        overlays: Vec::new(),
+        layers: Vec::new(),
+        photo_slots: vec![PhotoSlot::new(
+            (width - scale(PHOTO_WIDTH)) / 2,
+            photo_y,
+            scale(PHOTO_WIDTH),
+            scale(PHOTO_HEIGHT),
+        )],
+        text: vec![
+            text_line(
+                TextField::GroupName,
+                FontStyle::Regular,
+                font_size(80.0),
+                below_photo + scale(80),
+                width,
+            ),
+            text_line(
+                TextField::Headline,
+                FontStyle::Regular,
+                font_size(70.0),
+                below_photo + scale(225),
+                width,
+            ),
+            TextBox {
+                x: scale(50),
+                width: width - scale(100),
+                max_height: Some(height - scale(200) - story_y),
+                wrap: true,
+                ..text_line(
+                    TextField::Story,
+                    FontStyle::Italic,
+                    font_size(65.0),
+                    story_y,
+                    width,
+                )
+            },
+        ],
+        effects: Vec::new(),
+        class_effects: HashMap::new(),
+        effect_seed: 0,
+    }
+}
+
+/// A 2x6" strip: burst shots stacked top to bottom, the group name and headline below.
+/// There is no room for the story
+fn strip_layout() -> TemplateLayout {
+    let format = PrintFormat::Strip2x6;
+    let (width, height) = format.copy_size();
+    let slot_width = width - 2 * STRIP_MARGIN;
+    let slot_height = slot_width * 2 / 3;
+    let below_slots = STRIP_MARGIN + STRIP_SHOTS * slot_height + (STRIP_SHOTS - 1) * STRIP_GAP;
+
+    TemplateLayout {
+        format,
+        canvas: Canvas {
+            width,
+            height,
+            dpi: PRINT_DPI,
+            background_color: white(),
+        },
+        background: Vec::new(),
+        overlays: Vec::new(),
+        layers: Vec::new(),
+        photo_slots: (0..STRIP_SHOTS)
+            .map(|i| {
+                PhotoSlot::new(
+                    STRIP_MARGIN,
+                    STRIP_MARGIN + i * (slot_height + STRIP_GAP),
+                    slot_width,
+                    slot_height,
+                )
+            })
+            .collect(),
+        text: vec![
+            text_line(
+                TextField::GroupName,
+                FontStyle::Regular,
+                56.0,
+                below_slots + 50,
+                width,
+            ),
+            text_line(
+                TextField::Headline,
+                FontStyle::Regular,
+                44.0,
+                below_slots + 150,
+                width,
+            ),
+        ],
+        effects: Vec::new(),
+        class_effects: HashMap::new(),
+        effect_seed: 0,
+    }
+}
+
+/// The last layout file read, kept until the file changes on disk so a print doesn't
+/// re-parse it but a designer's edits still show up on the next one
+#[derive(Debug, Clone, Default)]
+pub struct LayoutCache(Arc<Mutex<Option<CachedLayout>>>);
+
+#[derive(Debug)]
+struct CachedLayout {
+    path: PathBuf,
+    modified: SystemTime,
+    layout: TemplateLayout,
+}
+
+impl LayoutCache {
+    pub fn load(&self, path: &Path) -> Result<TemplateLayout, TemplateError> {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut cached = self.0.lock().unwrap();
+        if let Some(hit) = cached
+            .as_ref()
+            .filter(|c| c.path == path && Some(c.modified) == modified)
+        {
+            return Ok(hit.layout.clone());
+        }
+
+        let layout = TemplateLayout::load(path)?;
+        *cached = modified.map(|modified| CachedLayout {
+            path: path.to_path_buf(),
+            modified,
+            layout: layout.clone(),
+        });
+        Ok(layout)
+    }
+}
+
+impl TemplateLayout {
+    /// The built-in layout for `format`
+    pub fn for_format(format: PrintFormat) -> Self {
+        match format {
+            PrintFormat::Strip2x6 => strip_layout(),
+            _ => poster_layout(format),
+        }
+    }
+
+    /// Read a layout from a `.toml` or `.json` file. Relative image and font file paths
+    /// are taken from the layout file's directory
+    pub fn load(path: &Path) -> Result<Self, TemplateError> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| TemplateError::LayoutError(format!("{}: {}", path.display(), e)))?;
+        let mut layout = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&source),
+            _ => Self::from_toml(&source),
+        }
+        .map_err(|e| TemplateError::LayoutError(format!("{}: {}", path.display(), e)))?;
+
+        if let Some(dir) = path.parent() {
+            let resolve = |p: &mut PathBuf| {
+                if p.is_relative() {
+                    *p = dir.join(&*p);
+                }
+            };
+            for layer in layout
+                .background
+                .iter_mut()
+                .chain(layout.overlays.iter_mut())
+                .chain(layout.layers.iter_mut())
+            {
+                resolve(&mut layer.image);
+            }
+            for text in layout.text.iter_mut() {
+                if fonts::is_font_path(&text.font) && Path::new(&text.font).is_relative() {
+                    text.font = dir.join(&text.font).to_string_lossy().into_owned();
+                }
+            }
+        }
+        Ok(layout)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let layout: Self = toml::from_str(source).map_err(|e| e.to_string())?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, String> {
+        let layout: Self = serde_json::from_str(source).map_err(|e| e.to_string())?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    /// Share the photo area between several shots. Layouts that already have more than
+    /// one slot keep their own, and an area too small to split keeps its one slot
+    pub fn with_collage(mut self, collage: Collage) -> Self {
+        if let [area] = self.photo_slots[..] {
+            let slots = collage.slots(area);
+            if slots.iter().any(|s| s.width == 0 || s.height == 0) {
+                warn!(
+                    "Photo area of {}x{} is too small for a {:?} collage, printing one photo",
+                    area.width, area.height, collage
+                );
+            } else {
+                self.photo_slots = slots;
+            }
+        }
+        self
+    }
+
+    /// Pick the effect chain for a session's class and seed it, so reprinting the
+    /// session gives the same grain and tears
+    pub fn with_effects_for(mut self, class: Option<i32>, seed: u64) -> Self {
+        if let Some(chain) = class.and_then(|c| self.class_effects.get(&c.to_string())) {
+            self.effects = chain.clone();
+        }
+        self.effect_seed = seed;
+        self
+    }
+
+    /// Use `path` as a full-canvas background when the layout brings none of its own
+    pub fn with_default_background(mut self, path: PathBuf) -> Self {
+        if self.background.is_empty() {
+            self.background.push(Layer {
+                image: path,
+                x: 0,
+                y: 0,
+                width: None,
+                height: None,
+                z: None,
+                opacity: 1.0,
+                blend: BlendMode::Normal,
+            });
+        }
+        self
+    }
+
+    /// Everything drawn on the canvas, bottom to top, with its z. At the same z the
+    /// photos and text go first and layers keep their order in the file
+    pub fn stack(&self) -> Vec<(i32, StackItem<'_>)> {
+        let mut stack = vec![(PHOTO_Z, StackItem::Photos), (TEXT_Z, StackItem::Text)];
+        stack.extend(
+            self.background
+                .iter()
+                .map(|layer| (layer.z.unwrap_or(PHOTO_Z - 1), StackItem::Layer(layer))),
+        );
+        stack.extend(
+            self.overlays
+                .iter()
+                .chain(self.layers.iter())
+                .map(|layer| (layer.z.unwrap_or(PHOTO_Z + 1), StackItem::Layer(layer))),
+        );
+        stack.sort_by_key(|(z, _)| *z);
+        stack
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let Canvas { width, height, .. } = self.canvas;
+        if width == 0 || height == 0 {
+            return Err("canvas width and height must be positive".to_string());
+        }
+        if self.photo_slots.is_empty() {
+            return Err("layout needs at least one photo slot".to_string());
+        }
+        for (i, slot) in self.photo_slots.iter().enumerate() {
+            if slot.width == 0
+                || slot.height == 0
+                || slot.x + slot.width > width
+                || slot.y + slot.height > height
+            {
+                return Err(format!("photo slot {} does not fit on the canvas", i + 1));
+            }
+        }
+        let layers = self
+            .background
+            .iter()
+            .chain(&self.overlays)
+            .chain(&self.layers);
+        for layer in layers {
+            if !(0.0..=1.0).contains(&layer.opacity) {
+                return Err(format!(
+                    "layer {} opacity must be between 0 and 1",
+                    layer.image.display()
+                ));
+            }
+        }
+        for (i, text) in self.text.iter().enumerate() {
+            if text.size <= 0.0
+                || text.min_size() <= 0.0
+                || text.x + text.width > width
+                || text.y >= height
+            {
+                return Err(format!("text box {} does not fit on the canvas", i + 1));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_layouts_parse() {
+        let toml = TemplateLayout::from_toml(include_str!("../layouts/example.toml")).unwrap();
+        assert_eq!((toml.canvas.width, toml.canvas.height), (1200, 1800));
+        assert_eq!(toml.photo_slots.len(), 1);
+        assert_eq!(toml.text[2].field, Some(TextField::Story));
+        assert!(toml.text[2].wrap);
+
+        let json = TemplateLayout::from_json(
+            r##"{
+                "canvas": { "width": 600, "height": 400, "dpi": 150, "background_color": "#000000" },
+                "photo_slots": [{ "x": 10, "y": 10, "width": 300, "height": 200 }],
+                "text": [{ "text": "Howdy", "font": "fonts/a.ttf", "size": 20,
+                           "align": "right", "x": 320, "y": 10, "width": 270 }]
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(json.canvas.background_color, Rgb([0, 0, 0]));
+        assert_eq!(json.text[0].align, TextAlign::Right);
+        assert_eq!(json.text[0].color, dark_gray());
+    }
+
+    #[test]
+    fn test_built_in_layouts_fit_their_format() {
+        for format in PrintFormat::ALL {
+            let layout = TemplateLayout::for_format(format);
+            assert_eq!(
+                (layout.canvas.width, layout.canvas.height),
+                format.copy_size()
+            );
+            layout.validate().unwrap();
+        }
+        assert_eq!(
+            TemplateLayout::for_format(PrintFormat::Strip2x6)
+                .photo_slots
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_class_effects_replace_the_default_chain() {
+        let layout = TemplateLayout::from_toml(
+            r##"
+            [canvas]
+            width = 600
+            height = 400
+            dpi = 150
+
+            [[photo_slots]]
+            x = 0
+            y = 0
+            width = 300
+            height = 200
+
+            [[effects]]
+            effect = "sepia"
+
+            [[class_effects.2]]
+            effect = "grain"
+            amount = 0.1
+            "##,
+        )
+        .unwrap();
+
+        let gunslinger = layout.clone().with_effects_for(Some(0), 9);
+        assert_eq!(gunslinger.effects, vec![PhotoEffect::Sepia { amount: 1.0 }]);
+        assert_eq!(gunslinger.effect_seed, 9);
+        let third = layout.with_effects_for(Some(2), 9);
+        assert_eq!(third.effects, vec![PhotoEffect::Grain { amount: 0.1 }]);
+    }
+
+    #[test]
+    fn test_layers_stack_around_photos_and_text() {
+        let layout = TemplateLayout::from_toml(
+            r#"
+            [canvas]
+            width = 600
+            height = 400
+            dpi = 150
+
+            [[photo_slots]]
+            x = 0
+            y = 0
+            width = 300
+            height = 200
+
+            [[background]]
+            image = "paper.png"
+
+            [[overlays]]
+            image = "nails.png"
+
+            [[layers]]
+            image = "stamp.png"
+            z = 150
+            opacity = 0.8
+            blend = "multiply"
+
+            [[layers]]
+            image = "shadow.png"
+            z = -5
+            "#,
+        )
+        .unwrap();
+
+        let order: Vec<String> = layout
+            .stack()
+            .iter()
+            .map(|(_, item)| match item {
+                StackItem::Layer(layer) => layer.image.display().to_string(),
+                StackItem::Photos => "photos".to_string(),
+                StackItem::Text => "text".to_string(),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "shadow.png",
+                "paper.png",
+                "photos",
+                "nails.png",
+                "text",
+                "stamp.png"
+            ]
+        );
+        assert_eq!(layout.layers[0].blend, BlendMode::Multiply);
+        assert_eq!(layout.overlays[0].opacity, 1.0);
+    }
+
+    #[test]
+    fn test_collages_split_the_photo_area() {
+        let poster = TemplateLayout::default();
+        let area = poster.photo_slots[0];
+
+        for (collage, count) in [(Collage::Grid, 4), (Collage::Strip, 3), (Collage::Hero, 4)] {
+            let layout = poster.clone().with_collage(collage);
+            assert_eq!(layout.photo_slots.len(), count);
+            layout.validate().unwrap();
+            for slot in &layout.photo_slots {
+                assert!(slot.x >= area.x && slot.x + slot.width <= area.x + area.width);
+                assert!(slot.y >= area.y && slot.y + slot.height <= area.y + area.height);
+            }
+        }
+
+        let hero = poster.clone().with_collage(Collage::Hero).photo_slots;
+        assert_eq!(hero[0].height, area.height);
+        assert!(hero[0].width > 2 * hero[1].width - SLOT_GAP);
+
+        // A strip already has its own slots
+        let strip = TemplateLayout::for_format(PrintFormat::Strip2x6);
+        assert_eq!(
+            strip.clone().with_collage(Collage::Grid).photo_slots,
+            strip.photo_slots
+        );
+
+        // Too small to split: the one slot stays
+        let tiny = TemplateLayout {
+            photo_slots: vec![PhotoSlot::new(10, 10, 15, 15)],
+            ..TemplateLayout::default()
+        };
+        for collage in [Collage::Grid, Collage::Strip, Collage::Hero] {
+            assert_eq!(
+                tiny.clone().with_collage(collage).photo_slots,
+                tiny.photo_slots
+            );
+        }
+    }
+
+    #[test]
+    fn test_slots_off_the_canvas_are_rejected() {
+        let err = TemplateLayout::from_toml(
+            r#"
+            [canvas]
+            width = 600
+            height = 400
+            dpi = 150
+
+            [[photo_slots]]
+            x = 400
+            y = 0
+            width = 300
+            height = 200
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.contains("photo slot 1"));
+    }
+}
@@ -1,25 +1,36 @@
 // Template functionality for photo booth prints
 
-use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, ImageBuffer, Rgb, RgbImage, RgbaImage};
 use imageproc::drawing::draw_text_mut;
-use rusttype::{Font, Scale};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
-
-// Constants for a 4x6" print at 300 DPI
-const PRINT_WIDTH: u32 = 1200; // 4 inches * 300 DPI
-const PRINT_HEIGHT: u32 = 1800; // 6 inches * 300 DPI
-
-// Define the area for the photo within the template
-const PHOTO_WIDTH: u32 = 1000; // Leave room for borders
-const PHOTO_HEIGHT: u32 = 667; // Maintain 3:2 aspect ratio
-const PHOTO_Y_POSITION: u32 = 300; // Position for photo
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::fonts::{self, FontRegistry};
+use crate::orientation::open_upright;
+use crate::photo_effects::apply_effects;
+use crate::print_format::PrintFormat;
+use crate::template_layout::{
+    BlendMode, Layer, PhotoSlot, StackItem, TemplateLayout, TextAlign, TextBox, TextField,
+};
+use crate::text_layout::{fit_text, measure_width};
+
+// Size of the photo area in the built-in layout
+pub const PHOTO_WIDTH: u32 = 1000; // Leave room for borders
+pub const PHOTO_HEIGHT: u32 = 667; // Maintain 3:2 aspect ratio
+pub const SLOT_GAP: u32 = 20; // Space between shots in a grid or collage
 
 #[derive(Debug)]
 pub enum TemplateError {
     ImageLoadError(String),
     ImageSaveError(String),
     CompositionError(String),
+    LayoutError(String),
+    FontError(String),
 }
 
 impl fmt::Display for TemplateError {
@@ -28,19 +39,40 @@ impl fmt::Display for TemplateError {
             TemplateError::ImageLoadError(msg) => write!(f, "Failed to load image: {}", msg),
             TemplateError::ImageSaveError(msg) => write!(f, "Failed to save image: {}", msg),
             TemplateError::CompositionError(msg) => write!(f, "Composition error: {}", msg),
+            TemplateError::LayoutError(msg) => write!(f, "Invalid template layout: {}", msg),
+            TemplateError::FontError(msg) => write!(f, "Font error: {}", msg),
         }
     }
 }
 
 impl Error for TemplateError {}
 
+/// How a photo fills a slot whose aspect ratio differs from its own
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum PhotoFit {
+    /// Fill the slot, cropping the overflow evenly from both sides
+    Cover,
+    /// Show the whole photo, filling the rest of the slot with `letterbox`
+    Contain { letterbox: [u8; 3] },
+    /// Fill the slot, cropping around a point given as fractions (0.0-1.0) of the
+    /// photo's width and height
+    Focal { x: f32, y: f32 },
+}
+
+/// A stack entry ready to draw: layer images are read once per render
+enum Drawable<'a> {
+    Layer(&'a Layer, RgbaImage),
+    Text,
+}
+
 pub struct PrintTemplate {
     story_text: String,
     group_name: String,
     headline: String,
-    background_color: Rgb<u8>,
-    text_color: Rgb<u8>,
-    background_path: Option<String>,
+    layout: TemplateLayout,
+    fit: PhotoFit,
+    fonts: Arc<FontRegistry>,
 }
 
 impl Default for PrintTemplate {
@@ -49,9 +81,10 @@ impl Default for PrintTemplate {
             story_text: "STORY HERE".to_string(),
             group_name: String::new(),
             headline: String::new(),
-            background_color: Rgb([255, 255, 255]), // White background
-            text_color: Rgb([50, 50, 50]),          // Dark gray text
-            background_path: None,
+            layout: TemplateLayout::default(),
+            fit: PhotoFit::Cover,
+            // Only the built-in font until `with_fonts` hands over the app's registry
+            fonts: fonts::built_in_only(),
         }
     }
 }
@@ -64,8 +97,8 @@ impl PrintTemplate {
         }
     }
 
-    pub fn with_background(mut self, path: &str) -> Self {
-        self.background_path = Some(path.to_string());
+    pub fn with_layout(mut self, layout: TemplateLayout) -> Self {
+        self.layout = layout;
         self
     }
 
@@ -79,18 +112,41 @@ impl PrintTemplate {
         self
     }
 
-    pub fn apply_to_photo(&self, photo_path: &str, output_path: &str) -> Result<(), TemplateError> {
-        if !std::path::Path::new(photo_path).exists() {
-            return Err(TemplateError::ImageLoadError(format!(
-                "Photo file does not exist: {}",
-                photo_path
-            )));
+    pub fn with_fit(mut self, fit: PhotoFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    pub fn with_fonts(mut self, fonts: Arc<FontRegistry>) -> Self {
+        self.fonts = fonts;
+        self
+    }
+
+    /// Render several shots (e.g. a burst) into the photo area, one slot per shot in order
+    pub fn apply_to_photos(
+        &self,
+        photo_paths: &[&str],
+        output_path: &str,
+    ) -> Result<(), TemplateError> {
+        if photo_paths.is_empty() {
+            return Err(TemplateError::CompositionError(
+                "No photos to place in template".to_string(),
+            ));
         }
 
-        let photo =
-            image::open(photo_path).map_err(|e| TemplateError::ImageLoadError(e.to_string()))?;
+        let mut photos = Vec::with_capacity(photo_paths.len());
+        for photo_path in photo_paths {
+            if !std::path::Path::new(photo_path).exists() {
+                return Err(TemplateError::ImageLoadError(format!(
+                    "Photo file does not exist: {}",
+                    photo_path
+                )));
+            }
+            // Cameras often store portrait shots sideways with an EXIF orientation tag
+            photos.push(open_upright(photo_path).map_err(TemplateError::ImageLoadError)?);
+        }
 
-        let templated = self.compose_template(photo)?;
+        let templated = self.compose_template(photos)?;
 
         templated
             .save(output_path)
@@ -99,233 +155,824 @@ impl PrintTemplate {
         Ok(())
     }
 
-    fn compose_template(&self, photo: DynamicImage) -> Result<RgbImage, TemplateError> {
-        // 1. Load the background image
-        let mut canvas = if let Some(bg_path) = &self.background_path {
-            match image::open(bg_path) {
-                Ok(bg) => {
-                    // Resize background to match print dimensions
-                    image::imageops::resize(
-                        &bg.to_rgb8(),
-                        PRINT_WIDTH,
-                        PRINT_HEIGHT,
-                        image::imageops::FilterType::Lanczos3,
-                    )
-                }
-                Err(_) => {
-                    // Fallback to solid color if background can't be loaded
-                    ImageBuffer::from_pixel(PRINT_WIDTH, PRINT_HEIGHT, self.background_color)
+    /// Render live view frames into the poster as a looping animated GIF `width` pixels wide.
+    /// Everything under the photo is drawn once; each frame redraws the photo and what
+    /// sits above it
+    pub fn apply_to_animation(
+        &self,
+        frames: Vec<DynamicImage>,
+        frame_delay_ms: u32,
+        width: u32,
+        output_path: &str,
+    ) -> Result<(), TemplateError> {
+        if frames.is_empty() {
+            return Err(TemplateError::CompositionError(
+                "No frames to place in template".to_string(),
+            ));
+        }
+
+        let (under, over) = self.prepare_stack();
+        let mut poster = self.blank_canvas();
+        self.draw_stack(&mut poster, &under);
+        let slots = self.slots_for(1);
+        let height = width * self.layout.canvas.height / self.layout.canvas.width;
+
+        let file = std::fs::File::create(output_path)
+            .map_err(|e| TemplateError::ImageSaveError(e.to_string()))?;
+        let mut encoder = GifEncoder::new_with_speed(std::io::BufWriter::new(file), 10);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| TemplateError::ImageSaveError(e.to_string()))?;
+
+        for frame in frames {
+            let mut canvas = poster.clone();
+            for slot in &slots {
+                let scaled = self.scale_photo_to_fit(&frame, slot)?;
+                self.place_photo(&mut canvas, &scaled, slot, 0);
+            }
+            // Frames, stickers and text stay on top of the moving photo
+            self.draw_stack(&mut canvas, &over);
+
+            let canvas = image::imageops::resize(
+                &DynamicImage::ImageRgb8(canvas).to_rgba8(),
+                width,
+                height,
+                image::imageops::FilterType::Triangle,
+            );
+            encoder
+                .encode_frame(Frame::from_parts(
+                    canvas,
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(frame_delay_ms, 1),
+                ))
+                .map_err(|e| TemplateError::ImageSaveError(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    fn compose_template(&self, photos: Vec<DynamicImage>) -> Result<RgbImage, TemplateError> {
+        let canvas_spec = &self.layout.canvas;
+        debug!(
+            "Composing {}x{} print at {} DPI",
+            canvas_spec.width, canvas_spec.height, canvas_spec.dpi
+        );
+        let (under, over) = self.prepare_stack();
+        let mut canvas = self.blank_canvas();
+
+        // 1. Layers under the photos
+        self.draw_stack(&mut canvas, &under);
+
+        // 2. Scale the photos to fit the slots, in order, and place them onto the canvas.
+        // With fewer photos than slots they repeat, so a collage is never left with gaps
+        if !photos.is_empty() {
+            let slots = self.slots_for(photos.len());
+            for (i, slot) in slots.iter().enumerate() {
+                let scaled_photo = self.scale_photo_to_fit(&photos[i % photos.len()], slot)?;
+                self.place_photo(&mut canvas, &scaled_photo, slot, i);
+            }
+        }
+
+        // 3. Layers and text over the photos, in z order
+        self.draw_stack(&mut canvas, &over);
+
+        Ok(canvas)
+    }
+
+    fn blank_canvas(&self) -> RgbImage {
+        let canvas_spec = &self.layout.canvas;
+        ImageBuffer::from_pixel(
+            canvas_spec.width,
+            canvas_spec.height,
+            canvas_spec.background_color,
+        )
+    }
+
+    /// Read the layout's layers and split the stack into what goes under the photos and
+    /// what goes over them. A layer that can't be read is skipped so a missing file
+    /// never stops a print
+    fn prepare_stack(&self) -> (Vec<Drawable<'_>>, Vec<Drawable<'_>>) {
+        let (mut under, mut over) = (Vec::new(), Vec::new());
+        let mut above_photos = false;
+        for (_, item) in self.layout.stack() {
+            let drawable = match item {
+                StackItem::Photos => {
+                    above_photos = true;
+                    continue;
                 }
+                StackItem::Text => Drawable::Text,
+                StackItem::Layer(layer) => match self.load_layer(layer) {
+                    Some(image) => Drawable::Layer(layer, image),
+                    None => continue,
+                },
+            };
+            if above_photos {
+                over.push(drawable);
+            } else {
+                under.push(drawable);
             }
-        } else {
-            // Fallback to solid color if background doesn't exist
-            ImageBuffer::from_pixel(PRINT_WIDTH, PRINT_HEIGHT, self.background_color)
-        };
+        }
+        (under, over)
+    }
 
-        // 2. Scale the photo to fit its designated area
-        let scaled_photo = self.scale_photo_to_fit(photo)?;
+    fn draw_stack(&self, canvas: &mut RgbImage, stack: &[Drawable]) {
+        for drawable in stack {
+            match drawable {
+                Drawable::Layer(layer, image) => {
+                    let (x, y) = (layer.x as i64, layer.y as i64);
+                    composite(canvas, image, x, y, layer.opacity, layer.blend)
+                }
+                Drawable::Text => {
+                    for text_box in &self.layout.text {
+                        self.draw_text_box(canvas, text_box);
+                    }
+                }
+            }
+        }
+    }
 
-        // 3. Place the scaled photo onto the canvas
-        self.place_photo(&mut canvas, &scaled_photo);
+    /// Slots for `count` photos: all of the layout's slots, or, when there are more
+    /// photos than slots, a grid over the area the slots cover together so no shot is
+    /// squeezed into one cell or left out
+    fn slots_for(&self, count: usize) -> Vec<PhotoSlot> {
+        let slots = &self.layout.photo_slots;
+        if count <= slots.len() {
+            return slots.clone();
+        }
+        let left = slots.iter().map(|s| s.x).min().unwrap_or(0);
+        let top = slots.iter().map(|s| s.y).min().unwrap_or(0);
+        let right = slots.iter().map(|s| s.x + s.width).max().unwrap_or(0);
+        let bottom = slots.iter().map(|s| s.y + s.height).max().unwrap_or(0);
+        let area = PhotoSlot {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+            // One slot's tilt makes no sense across the whole grid
+            rotation: 0.0,
+            ..slots[0]
+        };
+        grid_slots(area, count)
+    }
 
-        // 4. Add group name below the photo
-        self.add_group_name_text(&mut canvas)?;
+    /// Read a layer's image at its size on the canvas
+    fn load_layer(&self, layer: &Layer) -> Option<RgbaImage> {
+        let image = match image::open(&layer.image) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Skipping layer {}: {}", layer.image.display(), e);
+                return None;
+            }
+        };
+        let width = layer.width.unwrap_or(self.layout.canvas.width);
+        let height = layer.height.unwrap_or(self.layout.canvas.height);
+        let image = if image.width() == width && image.height() == height {
+            image.to_rgba8()
+        } else {
+            image::imageops::resize(
+                &image.to_rgba8(),
+                width,
+                height,
+                image::imageops::FilterType::Lanczos3,
+            )
+        };
+        Some(image)
+    }
 
-        // 5. Add headline above the story
-        self.add_headline_text(&mut canvas)?;
+    fn draw_text_box(&self, canvas: &mut RgbImage, text_box: &TextBox) {
+        let text = match text_box.field {
+            Some(TextField::GroupName) => &self.group_name,
+            Some(TextField::Headline) => &self.headline,
+            Some(TextField::Story) => &self.story_text,
+            None => &text_box.text,
+        };
+        if text.trim().is_empty() {
+            return;
+        }
 
-        // 6. Add story text
-        self.add_story_text(&mut canvas)?;
+        let font = self.fonts.font_or_fallback(&text_box.font, text_box.style);
 
-        Ok(canvas)
+        let fitted = fit_text(&font, text, text_box);
+        for (i, line) in fitted.lines.iter().enumerate() {
+            let line_width = measure_width(&font, line, fitted.scale);
+            let x = match text_box.align {
+                TextAlign::Left => text_box.x,
+                TextAlign::Center => text_box.x + text_box.width.saturating_sub(line_width) / 2,
+                TextAlign::Right => text_box.x + text_box.width.saturating_sub(line_width),
+            };
+            draw_text_mut(
+                canvas,
+                text_box.color,
+                x as i32,
+                (text_box.y + i as u32 * fitted.line_height) as i32,
+                fitted.scale,
+                &font,
+                line,
+            );
+        }
     }
 
-    fn scale_photo_to_fit(&self, photo: DynamicImage) -> Result<RgbImage, TemplateError> {
+    /// Scale `photo` to `slot` with the slot's own fit, or the template's
+    fn scale_photo_to_fit(
+        &self,
+        photo: &DynamicImage,
+        slot: &PhotoSlot,
+    ) -> Result<RgbImage, TemplateError> {
+        let (width, height) = (slot.width, slot.height);
         let photo_rgb = photo.to_rgb8();
         if photo_rgb.width() == 0 || photo_rgb.height() == 0 {
             return Err(TemplateError::CompositionError(
                 "Invalid photo dimensions".to_string(),
             ));
         }
-        let scaled = image::imageops::resize(
-            &photo_rgb,
-            PHOTO_WIDTH,
-            PHOTO_HEIGHT,
-            image::imageops::FilterType::Lanczos3,
-        );
+        let scaled = match slot.fit.unwrap_or(self.fit) {
+            PhotoFit::Cover => crop_to_fill(&photo_rgb, width, height, 0.5, 0.5),
+            PhotoFit::Focal { x, y } => crop_to_fill(&photo_rgb, width, height, x, y),
+            PhotoFit::Contain { letterbox } => {
+                letterbox_to_fit(&photo_rgb, width, height, Rgb(letterbox))
+            }
+        };
         Ok(scaled)
     }
 
-    fn place_photo(&self, canvas: &mut RgbImage, photo: &RgbImage) {
-        let photo_x = (PRINT_WIDTH - photo.width()) / 2;
-        let photo_y = PHOTO_Y_POSITION;
-        image::imageops::overlay(canvas, photo, photo_x as i64, photo_y as i64);
+    /// Age the photo with the layout's effects, turn it to the slot's angle, then put it
+    /// in its slot. Each shot of a burst gets its own grain and tears
+    fn place_photo(&self, canvas: &mut RgbImage, photo: &RgbImage, slot: &PhotoSlot, shot: usize) {
+        if self.layout.effects.is_empty() && slot.rotation == 0.0 {
+            image::imageops::overlay(canvas, photo, slot.x as i64, slot.y as i64);
+            return;
+        }
+        let seed = self.layout.effect_seed.wrapping_add(shot as u64);
+        let aged = apply_effects(photo, &self.layout.effects, seed);
+        if slot.rotation == 0.0 {
+            blend_onto(canvas, &aged, slot.x as i64, slot.y as i64);
+            return;
+        }
+
+        // Keep the turned photo centred on the slot
+        let turned = rotate_photo(&aged, slot.rotation);
+        let x = (slot.x + slot.width / 2) as i64 - (turned.width() / 2) as i64;
+        let y = (slot.y + slot.height / 2) as i64 - (turned.height() / 2) as i64;
+        blend_onto(canvas, &turned, x, y);
     }
+}
 
-    fn add_story_text(&self, canvas: &mut RgbImage) -> Result<(), TemplateError> {
-        let font_data = match std::fs::read("/usr/local/share/fonts/IMFellEnglish-Italic.ttf") {
-            Ok(data) => data,
-            Err(_) => {
-                println!("Warning: Font not found. Skipping text.");
-                return Ok(());
-            }
-        };
-        let font = match Font::try_from_bytes(&font_data) {
-            Some(f) => f,
-            None => {
-                println!("Warning: Failed to parse font. Skipping text.");
-                return Ok(());
-            }
-        };
+/// Lay `count` photos out in a centred grid of 3:2 cells inside `area`
+fn grid_slots(area: PhotoSlot, count: usize) -> Vec<PhotoSlot> {
+    if count <= 1 {
+        return vec![area];
+    }
 
-        let story_scale = Scale { x: 65.0, y: 65.0 };
+    let count = count as u32;
+    let cols = (count as f64).sqrt().ceil() as u32;
+    let rows = count.div_ceil(cols);
+    let cell_width = area.width.saturating_sub(SLOT_GAP * (cols - 1)) / cols;
+    let cell_height = area.height.saturating_sub(SLOT_GAP * (rows - 1)) / rows;
+
+    // Keep every shot at 3:2 inside its cell
+    let (width, height) = if cell_width * 2 / 3 <= cell_height {
+        (cell_width, cell_width * 2 / 3)
+    } else {
+        (cell_height * 3 / 2, cell_height)
+    };
+    // An area too small to split takes the shots one at a time rather than as empty cells
+    if width == 0 || height == 0 {
+        warn!(
+            "Photo area of {}x{} is too small for {} shots, printing one",
+            area.width, area.height, count
+        );
+        return vec![area];
+    }
 
-        // Story text positioned below the photo
-        let story_lines = self.wrap_text(&font, &self.story_text, story_scale, PRINT_WIDTH - 100);
-        let line_height = 45; // Space between lines
+    let grid_height = rows * height + (rows - 1) * SLOT_GAP;
+    let top = area.y + area.height.saturating_sub(grid_height) / 2;
 
-        // Position story text below the photo with some padding
-        let story_start_y = PHOTO_Y_POSITION + PHOTO_HEIGHT + 350;
+    (0..count)
+        .map(|i| {
+            let row = i / cols;
+            let col = i % cols;
+            // Center a short last row
+            let in_row = if row == rows - 1 {
+                count - row * cols
+            } else {
+                cols
+            };
+            let row_width = in_row * width + (in_row - 1) * SLOT_GAP;
+            PhotoSlot {
+                x: area.x + area.width.saturating_sub(row_width) / 2 + col * (width + SLOT_GAP),
+                y: top + row * (height + SLOT_GAP),
+                width,
+                height,
+                ..area
+            }
+        })
+        .collect()
+}
 
-        for (i, line) in story_lines.iter().enumerate() {
-            let line_width = self.measure_text_width(&font, line, story_scale);
-            let line_y = story_start_y + (i as u32 * line_height);
+/// Alpha-blend `layer` onto `canvas` with its top-left corner at (`x`, `y`)
+fn blend_onto(canvas: &mut RgbImage, layer: &RgbaImage, x: i64, y: i64) {
+    composite(canvas, layer, x, y, 1.0, BlendMode::Normal);
+}
 
-            // Make sure we don't draw below the bottom of the canvas
-            if line_y < PRINT_HEIGHT - 200 {
-                draw_text_mut(
-                    canvas,
-                    self.text_color,
-                    ((PRINT_WIDTH - line_width) / 2) as i32,
-                    line_y as i32,
-                    story_scale,
-                    &font,
-                    line,
-                );
-            }
+/// Draw `layer` at (`x`, `y`), weighting its alpha by `opacity`. Whatever falls off the
+/// canvas is clipped
+fn composite(
+    canvas: &mut RgbImage,
+    layer: &RgbaImage,
+    x: i64,
+    y: i64,
+    opacity: f32,
+    blend: BlendMode,
+) {
+    for (lx, ly, pixel) in layer.enumerate_pixels() {
+        let (cx, cy) = (x + lx as i64, y + ly as i64);
+        if cx < 0 || cy < 0 || cx >= canvas.width() as i64 || cy >= canvas.height() as i64 {
+            continue;
+        }
+        let (cx, cy) = (cx as u32, cy as u32);
+        let alpha = pixel.0[3] as f32 / 255.0 * opacity;
+        if alpha <= 0.0 {
+            continue;
+        }
+        let below = canvas.get_pixel_mut(cx, cy);
+        for c in 0..3 {
+            let base = below.0[c] as f32;
+            let top = match blend {
+                BlendMode::Normal => pixel.0[c] as f32,
+                BlendMode::Multiply => pixel.0[c] as f32 * base / 255.0,
+            };
+            below.0[c] = (top * alpha + base * (1.0 - alpha)).round() as u8;
         }
-
-        Ok(())
     }
+}
 
-    fn add_group_name_text(&self, canvas: &mut RgbImage) -> Result<(), TemplateError> {
-        if self.group_name.is_empty() {
-            return Ok(());
-        }
+/// Turn `photo` `degrees` clockwise onto a canvas big enough to keep its corners and
+/// centred on it. The space around it is transparent
+fn rotate_photo(photo: &RgbaImage, degrees: f32) -> RgbaImage {
+    let (width, height) = (photo.width() as f32, photo.height() as f32);
+    let radians = degrees.to_radians();
+    let (sin, cos) = (radians.sin().abs(), radians.cos().abs());
+    // A quarter turn can make the box narrower or shorter than the photo, so pad to
+    // whichever is bigger on each side
+    let padded_width = ((width * cos + height * sin).ceil() as u32).max(photo.width());
+    let padded_height = ((width * sin + height * cos).ceil() as u32).max(photo.height());
+
+    let mut padded = RgbaImage::new(padded_width, padded_height);
+    image::imageops::replace(
+        &mut padded,
+        photo,
+        ((padded_width - photo.width()) / 2) as i64,
+        ((padded_height - photo.height()) / 2) as i64,
+    );
+    rotate_about_center(
+        &padded,
+        radians,
+        Interpolation::Bilinear,
+        image::Rgba([0, 0, 0, 0]),
+    )
+}
 
-        let font_data = match std::fs::read("/usr/local/share/fonts/IMFellEnglish-Regular.ttf") {
-            Ok(data) => data,
-            Err(_) => return Ok(()),
-        };
-        let font = match Font::try_from_bytes(&font_data) {
-            Some(f) => f,
-            None => return Ok(()),
+/// Scale `photo` to cover `width` x `height`, cropping the overflow so that the point at
+/// (`focal_x`, `focal_y`), as fractions of the photo, stays as close to the centre as the
+/// photo's edges allow
+fn crop_to_fill(photo: &RgbImage, width: u32, height: u32, focal_x: f32, focal_y: f32) -> RgbImage {
+    let (photo_width, photo_height) = photo.dimensions();
+
+    // Largest part of the photo with the slot's aspect ratio
+    let (crop_width, crop_height) =
+        if photo_width as u64 * height as u64 > photo_height as u64 * width as u64 {
+            let w = (photo_height as u64 * width as u64 / height as u64) as u32;
+            (w.max(1), photo_height)
+        } else {
+            let h = (photo_width as u64 * height as u64 / width as u64) as u32;
+            (photo_width, h.max(1))
         };
 
-        let group_scale = Scale { x: 80.0, y: 80.0 };
+    let offset = |focal: f32, full: u32, crop: u32| {
+        let centred = focal.clamp(0.0, 1.0) * full as f32 - crop as f32 / 2.0;
+        centred.clamp(0.0, (full - crop) as f32).round() as u32
+    };
+    let x = offset(focal_x, photo_width, crop_width);
+    let y = offset(focal_y, photo_height, crop_height);
+
+    let cropped = image::imageops::crop_imm(photo, x, y, crop_width, crop_height).to_image();
+    image::imageops::resize(
+        &cropped,
+        width,
+        height,
+        image::imageops::FilterType::Lanczos3,
+    )
+}
 
-        // Position group name below the photo
-        let group_y = PHOTO_Y_POSITION + PHOTO_HEIGHT + 80;
-        let group_width = self.measure_text_width(&font, &self.group_name, group_scale);
+/// Scale `photo` to fit inside `width` x `height`, centred on a `letterbox` background
+fn letterbox_to_fit(photo: &RgbImage, width: u32, height: u32, letterbox: Rgb<u8>) -> RgbImage {
+    let (photo_width, photo_height) = photo.dimensions();
+    let scale = f64::min(
+        width as f64 / photo_width as f64,
+        height as f64 / photo_height as f64,
+    );
+    let scaled_width = ((photo_width as f64 * scale).round() as u32).clamp(1, width);
+    let scaled_height = ((photo_height as f64 * scale).round() as u32).clamp(1, height);
+
+    let scaled = image::imageops::resize(
+        photo,
+        scaled_width,
+        scaled_height,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let mut slot = ImageBuffer::from_pixel(width, height, letterbox);
+    image::imageops::overlay(
+        &mut slot,
+        &scaled,
+        ((width - scaled_width) / 2) as i64,
+        ((height - scaled_height) / 2) as i64,
+    );
+    slot
+}
 
-        draw_text_mut(
-            canvas,
-            self.text_color,
-            ((PRINT_WIDTH - group_width) / 2) as i32,
-            group_y as i32,
-            group_scale,
-            &font,
-            &self.group_name,
-        );
+/// Parse a `#rrggbb` (or `rrggbb`) colour
+pub fn parse_hex_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
 
-        Ok(())
+/// Play `frames` forward and then back again, without repeating the turning points,
+/// so the GIF loops smoothly
+pub fn boomerang<T: Clone>(mut frames: Vec<T>) -> Vec<T> {
+    if frames.len() > 2 {
+        let back: Vec<T> = frames[1..frames.len() - 1].iter().rev().cloned().collect();
+        frames.extend(back);
     }
+    frames
+}
+
+/// Lay out the finished print at `copy_path` on the sheet `format` prints on: scaled to
+/// the print size and repeated for 2-up formats, turned sideways when the copies stack
+/// down a portrait sheet rather than across it
+pub fn impose_for_print(
+    copy_path: &str,
+    sheet_path: &str,
+    format: PrintFormat,
+) -> Result<(), TemplateError> {
+    let copy = image::open(copy_path)
+        .map_err(|e| TemplateError::ImageLoadError(format!("{}: {}", copy_path, e)))?;
+    impose(&copy.to_rgb8(), format)
+        .save(sheet_path)
+        .map_err(|e| TemplateError::ImageSaveError(e.to_string()))
+}
 
-    fn add_headline_text(&self, canvas: &mut RgbImage) -> Result<(), TemplateError> {
-        if self.headline.is_empty() {
-            return Ok(());
+fn impose(copy: &RgbImage, format: PrintFormat) -> RgbImage {
+    let (copy_width, copy_height) = format.copy_size();
+    let (sheet_width, sheet_height) = format.sheet_size();
+    let copies = format.copies_per_sheet();
+
+    let copy = if copy.dimensions() == (copy_width, copy_height) {
+        copy.clone()
+    } else {
+        image::imageops::resize(
+            copy,
+            copy_width,
+            copy_height,
+            image::imageops::FilterType::Lanczos3,
+        )
+    };
+
+    let mut sheet = ImageBuffer::from_pixel(sheet_width, sheet_height, Rgb([255, 255, 255]));
+    if copy_width * copies <= sheet_width {
+        for i in 0..copies {
+            image::imageops::replace(&mut sheet, &copy, (i * copy_width) as i64, 0);
+        }
+    } else {
+        let turned = image::imageops::rotate90(&copy);
+        for i in 0..copies {
+            image::imageops::replace(&mut sheet, &turned, 0, (i * copy_width) as i64);
         }
+    }
+    sheet
+}
 
-        let font_data = match std::fs::read("/usr/local/share/fonts/IMFellEnglish-Regular.ttf") {
-            Ok(data) => data,
-            Err(_) => return Ok(()),
-        };
-        let font = match Font::try_from_bytes(&font_data) {
-            Some(f) => f,
-            None => return Ok(()),
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_layout::Collage;
+
+    #[test]
+    fn test_single_photo_fills_photo_area() {
+        let template = PrintTemplate::default();
+        assert_eq!(
+            template.slots_for(1),
+            vec![PhotoSlot::new(100, 300, PHOTO_WIDTH, PHOTO_HEIGHT)]
+        );
+    }
 
-        let headline_scale = Scale { x: 70.0, y: 70.0 };
+    #[test]
+    fn test_focal_crop_keeps_aspect_and_focal_side() {
+        // Wide photo: black on the left half, white on the right
+        let photo = RgbImage::from_fn(400, 100, |x, _| {
+            if x < 200 {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        });
 
-        // Position headline above the story
-        let headline_y = PHOTO_Y_POSITION + PHOTO_HEIGHT + 225;
-        let headline_width = self.measure_text_width(&font, &self.headline, headline_scale);
+        let right = crop_to_fill(&photo, 100, 100, 1.0, 0.5);
+        assert_eq!(right.dimensions(), (100, 100));
+        assert!(right.pixels().all(|p| p.0[0] > 200));
 
-        draw_text_mut(
-            canvas,
-            self.text_color,
-            ((PRINT_WIDTH - headline_width) / 2) as i32,
-            headline_y as i32,
-            headline_scale,
-            &font,
-            &self.headline,
-        );
+        let left = crop_to_fill(&photo, 100, 100, 0.0, 0.5);
+        assert!(left.pixels().all(|p| p.0[0] < 50));
+    }
 
-        Ok(())
+    #[test]
+    fn test_contain_letterboxes_instead_of_stretching() {
+        let photo = RgbImage::from_pixel(200, 100, Rgb([255, 255, 255]));
+        let slot = letterbox_to_fit(&photo, 100, 100, Rgb([0, 0, 255]));
+        assert_eq!(slot.dimensions(), (100, 100));
+        // Bars above and below, photo in the middle at 100x50
+        assert_eq!(slot.get_pixel(50, 5), &Rgb([0, 0, 255]));
+        assert_eq!(slot.get_pixel(50, 50), &Rgb([255, 255, 255]));
+        assert_eq!(slot.get_pixel(50, 95), &Rgb([0, 0, 255]));
+
+        assert_eq!(parse_hex_color("#1a2B3c"), Some([0x1a, 0x2b, 0x3c]));
+        assert_eq!(parse_hex_color("red"), None);
     }
 
-    fn measure_text_width(&self, font: &Font, text: &str, scale: Scale) -> u32 {
-        font.layout(text, scale, rusttype::point(0.0, 0.0))
-            .last()
-            .and_then(|g| g.pixel_bounding_box())
-            .map_or(0, |bb| bb.max.x as u32)
+    #[test]
+    fn test_boomerang_plays_back_without_doubled_ends() {
+        assert_eq!(boomerang(vec![1, 2, 3, 4]), vec![1, 2, 3, 4, 3, 2]);
+        assert_eq!(boomerang(vec![1, 2]), vec![1, 2]);
     }
 
-    fn wrap_text(&self, font: &Font, text: &str, scale: Scale, max_width: u32) -> Vec<String> {
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let mut lines = Vec::new();
-        let mut current_line = String::new();
+    #[test]
+    fn test_animation_has_one_poster_per_frame() {
+        use image::AnimationDecoder;
 
-        for word in words {
-            let test_line = if current_line.is_empty() {
-                word.to_string()
-            } else {
-                format!("{} {}", current_line, word)
-            };
+        let output = std::env::temp_dir().join(format!("poster_{}.gif", uuid::Uuid::new_v4()));
+        let frames = vec![DynamicImage::new_rgb8(64, 48); 3];
+        PrintTemplate::new("")
+            .apply_to_animation(frames, 100, 120, output.to_str().unwrap())
+            .unwrap();
 
-            let width = self.measure_text_width(font, &test_line, scale);
+        let decoder =
+            image::codecs::gif::GifDecoder::new(std::fs::File::open(&output).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].buffer().dimensions(), (120, 180));
 
-            if width > max_width && !current_line.is_empty() {
-                // Current line is full, start a new one
-                lines.push(current_line);
-                current_line = word.to_string();
-            } else {
-                // Add word to current line
-                current_line = test_line;
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn test_burst_slots_stay_inside_photo_area() {
+        let template = PrintTemplate::default();
+        let area = template.slots_for(1)[0];
+        for count in 2..=6 {
+            let slots = template.slots_for(count);
+            assert_eq!(slots.len(), count);
+            for PhotoSlot {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } in slots
+            {
+                assert!(x >= area.x);
+                assert!(x + width <= area.x + area.width);
+                assert!(y >= area.y);
+                assert!(y + height <= area.y + area.height);
             }
         }
+    }
 
-        // Don't forget the last line
-        if !current_line.is_empty() {
-            lines.push(current_line);
-        }
+    #[test]
+    fn test_burst_in_a_tiny_photo_area_prints_one_shot() {
+        let mut layout = TemplateLayout::from_toml(
+            r##"
+            [canvas]
+            width = 100
+            height = 100
+            dpi = 100
+            background_color = "#000000"
+
+            [[photo_slots]]
+            x = 10
+            y = 10
+            width = 15
+            height = 15
+            "##,
+        )
+        .unwrap();
+        layout.text.clear();
+        let template = PrintTemplate::new("").with_layout(layout);
+        assert_eq!(template.slots_for(4).len(), 1);
+
+        let photos: Vec<DynamicImage> = (0..4)
+            .map(|_| DynamicImage::ImageRgb8(RgbImage::from_pixel(60, 40, Rgb([0, 0, 255]))))
+            .collect();
+        let canvas = template.compose_template(photos).unwrap();
+        assert_eq!(canvas.get_pixel(17, 17), &Rgb([0, 0, 255]));
+        assert_eq!(canvas.get_pixel(50, 50), &Rgb([0, 0, 0]));
+    }
 
-        lines
+    #[test]
+    fn test_two_up_formats_repeat_the_print() {
+        let strip = RgbImage::from_fn(600, 1800, |_, y| Rgb([(y / 8) as u8, 0, 0]));
+        let sheet = impose(&strip, PrintFormat::Strip2x6);
+        assert_eq!(sheet.dimensions(), (1200, 1800));
+        assert_eq!(sheet.get_pixel(10, 900), sheet.get_pixel(610, 900));
+
+        // Two 4x6 posters lie sideways on a 6x8 sheet
+        let poster = RgbImage::from_pixel(600, 900, Rgb([0, 0, 255]));
+        let sheet = impose(&poster, PrintFormat::Photo4x6TwoUp);
+        assert_eq!(sheet.dimensions(), (1800, 2400));
+        assert_eq!(sheet.get_pixel(900, 600), &Rgb([0, 0, 255]));
+        assert_eq!(sheet.get_pixel(900, 1800), &Rgb([0, 0, 255]));
     }
-}
 
-pub fn create_templated_print_with_background(
-    photo_path: &str,
-    output_path: &str,
-    story: &str,
-    group_name: &str,
-    headline: &str,
-    background_path: &str,
-) -> Result<(), TemplateError> {
-    // Ignoring header, footer, and break paths - just use background
-    let template = PrintTemplate::new(story)
-        .with_background(background_path)
-        .add_group_name(group_name)
-        .add_headline(headline);
-    template.apply_to_photo(photo_path, output_path)
+    #[test]
+    fn test_layout_slots_and_overlays_are_used() {
+        let dir = std::env::temp_dir().join(format!("layout_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Half-transparent red frame over the right half of the canvas
+        RgbaImage::from_pixel(50, 100, image::Rgba([255, 0, 0, 128]))
+            .save(dir.join("frame.png"))
+            .unwrap();
+        std::fs::write(
+            dir.join("layout.toml"),
+            r##"
+            [canvas]
+            width = 100
+            height = 100
+            dpi = 100
+            background_color = "#000000"
+
+            [[overlays]]
+            image = "frame.png"
+            x = 50
+            width = 50
+            height = 100
+
+            [[photo_slots]]
+            x = 0
+            y = 0
+            width = 100
+            height = 50
+            "##,
+        )
+        .unwrap();
+
+        let layout = TemplateLayout::load(&dir.join("layout.toml")).unwrap();
+        let template = PrintTemplate::new("").with_layout(layout);
+        let photo = DynamicImage::ImageRgb8(RgbImage::from_pixel(30, 20, Rgb([0, 0, 255])));
+        let canvas = template.compose_template(vec![photo]).unwrap();
+
+        assert_eq!(canvas.dimensions(), (100, 100));
+        assert!(canvas.get_pixel(10, 25).0[2] > 200);
+        assert_eq!(canvas.get_pixel(10, 75), &Rgb([0, 0, 0]));
+        let blended = canvas.get_pixel(75, 75).0;
+        assert!((120..=135).contains(&blended[0]) && blended[2] == 0);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_multiply_layers_darken_with_opacity() {
+        let mut canvas = RgbImage::from_pixel(2, 1, Rgb([200, 100, 50]));
+        let stamp = RgbaImage::from_pixel(2, 1, image::Rgba([128, 255, 0, 255]));
+
+        composite(&mut canvas, &stamp, 0, 0, 1.0, BlendMode::Multiply);
+        // White leaves the canvas alone, darker ink darkens it
+        assert_eq!(canvas.get_pixel(0, 0), &Rgb([100, 100, 0]));
+
+        let mut canvas = RgbImage::from_pixel(1, 1, Rgb([200, 100, 50]));
+        composite(&mut canvas, &stamp, 0, 0, 0.5, BlendMode::Normal);
+        assert_eq!(canvas.get_pixel(0, 0), &Rgb([164, 178, 25]));
+    }
+
+    #[test]
+    fn test_collage_slots_cycle_photos_with_their_own_fit_and_tilt() {
+        let mut layout = TemplateLayout::from_toml(
+            r##"
+            [canvas]
+            width = 300
+            height = 100
+            dpi = 100
+            background_color = "#000000"
+
+            [[photo_slots]]
+            x = 0
+            y = 0
+            width = 100
+            height = 100
+
+            [[photo_slots]]
+            x = 100
+            y = 0
+            width = 100
+            height = 100
+            fit = { mode = "contain", letterbox = [0, 255, 0] }
+
+            [[photo_slots]]
+            x = 200
+            y = 0
+            width = 100
+            height = 100
+            rotation = 45
+            "##,
+        )
+        .unwrap();
+        layout.text.clear();
+        let template = PrintTemplate::new("").with_layout(layout);
+        let wide = DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 100, Rgb([0, 0, 255])));
+        let canvas = template.compose_template(vec![wide]).unwrap();
+
+        // The one photo fills every slot: cropped, letterboxed, then turned
+        assert_eq!(canvas.get_pixel(50, 5), &Rgb([0, 0, 255]));
+        assert_eq!(canvas.get_pixel(150, 5), &Rgb([0, 255, 0]));
+        assert_eq!(canvas.get_pixel(150, 50), &Rgb([0, 0, 255]));
+        assert_eq!(canvas.get_pixel(250, 50), &Rgb([0, 0, 255]));
+        // The corner the turned photo no longer covers shows the canvas
+        assert_eq!(canvas.get_pixel(202, 2), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_quarter_and_slight_turns_of_a_wide_photo() {
+        let photo = RgbaImage::from_pixel(300, 200, image::Rgba([0, 0, 255, 255]));
+        let turned = rotate_photo(&photo, 90.0);
+        assert_eq!(turned.dimensions(), (300, 300));
+        assert_eq!(turned.get_pixel(150, 5)[3], 255);
+        assert_eq!(turned.get_pixel(5, 150)[3], 0);
+
+        let mut layout = TemplateLayout::from_toml(
+            r##"
+            [canvas]
+            width = 400
+            height = 300
+            dpi = 100
+            background_color = "#000000"
+
+            [[photo_slots]]
+            x = 0
+            y = 50
+            width = 150
+            height = 100
+            rotation = 90
+
+            [[photo_slots]]
+            x = 200
+            y = 50
+            width = 150
+            height = 100
+            rotation = 30
+            "##,
+        )
+        .unwrap();
+        layout.text.clear();
+        let template = PrintTemplate::new("").with_layout(layout);
+        let wide = DynamicImage::ImageRgb8(RgbImage::from_pixel(300, 200, Rgb([0, 0, 255])));
+        let canvas = template.compose_template(vec![wide]).unwrap();
+
+        // Stood on end: taller than its slot and clear of the slot's sides
+        assert_eq!(canvas.get_pixel(75, 40), &Rgb([0, 0, 255]));
+        assert_eq!(canvas.get_pixel(75, 160), &Rgb([0, 0, 255]));
+        assert_eq!(canvas.get_pixel(5, 100), &Rgb([0, 0, 0]));
+        // Tilted: still centred on its slot, with the slot's corners uncovered
+        assert_eq!(canvas.get_pixel(275, 100), &Rgb([0, 0, 255]));
+        assert_eq!(canvas.get_pixel(202, 52), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_burst_larger_than_collage_uses_the_whole_collage() {
+        let mut layout = TemplateLayout::default().with_collage(Collage::Grid);
+        layout.text.clear();
+        let cells = layout.photo_slots.clone();
+        let template = PrintTemplate::new("").with_layout(layout);
+
+        let slots = template.slots_for(6);
+        assert_eq!(slots.len(), 6);
+        // Spread over every cell of the grid, not packed into the first
+        let first = cells[0];
+        let last = cells[3];
+        assert!(slots.iter().any(|s| s.x >= last.x && s.y >= last.y));
+        assert!(slots.iter().all(|s| s.x >= first.x
+            && s.y >= first.y
+            && s.x + s.width <= last.x + last.width
+            && s.y + s.height <= last.y + last.height));
+
+        let photos: Vec<DynamicImage> = (0..6)
+            .map(|i| DynamicImage::ImageRgb8(RgbImage::from_pixel(30, 20, Rgb([40 * i, 0, 255]))))
+            .collect();
+        let canvas = template.compose_template(photos).unwrap();
+        for (i, slot) in slots.iter().enumerate() {
+            let centre = canvas.get_pixel(slot.x + slot.width / 2, slot.y + slot.height / 2);
+            assert_eq!(centre, &Rgb([40 * i as u8, 0, 255]));
+        }
+    }
 }
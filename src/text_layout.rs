@@ -0,0 +1,238 @@
+// Text box layout: keeps the caption's own line breaks, wraps to the box, shrinks the
+// font until everything fits and cuts what still doesn't with an ellipsis
+
+use rusttype::{Font, Scale};
+
+use crate::template_layout::TextBox;
+
+// Used when the box's font has no glyph for the single-character ellipsis
+const ELLIPSIS: &str = "\u{2026}";
+const ASCII_ELLIPSIS: &str = "...";
+// Each shrink step takes this much off the font size, and at least a pixel
+const SHRINK_STEP: f32 = 0.05;
+
+/// Lines ready to draw, at the size they fit at
+#[derive(Debug, Clone)]
+pub struct FittedText {
+    pub scale: Scale,
+    pub lines: Vec<String>,
+    pub line_height: u32,
+}
+
+/// Lay `text` out in `text_box`, shrinking from its size to its minimum and cutting the
+/// text short only when even the minimum doesn't fit
+pub fn fit_text(font: &Font, text: &str, text_box: &TextBox) -> FittedText {
+    let paragraphs: Vec<&str> = text
+        .trim()
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+    let min_size = text_box.min_size().min(text_box.size);
+
+    let mut size = text_box.size;
+    loop {
+        let fitted = lay_out(font, &paragraphs, text_box, size);
+        if fits(font, &fitted, text_box) {
+            return fitted;
+        }
+        if size <= min_size {
+            return truncate(font, fitted, text_box);
+        }
+        size = (size - (size * SHRINK_STEP).max(1.0)).max(min_size);
+    }
+}
+
+pub fn measure_width(font: &Font, text: &str, scale: Scale) -> u32 {
+    font.layout(text, scale, rusttype::point(0.0, 0.0))
+        .filter_map(|g| g.pixel_bounding_box())
+        .map(|bb| bb.max.x.max(0) as u32)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Distance between baselines: the box's own line height scaled with the font, or the
+/// font's ascent, descent and line gap
+fn line_height(font: &Font, text_box: &TextBox, size: f32) -> u32 {
+    match text_box.line_height {
+        Some(height) => (height as f32 * size / text_box.size).round() as u32,
+        None => {
+            let metrics = font.v_metrics(Scale::uniform(size));
+            (metrics.ascent - metrics.descent + metrics.line_gap).ceil() as u32
+        }
+    }
+    .max(1)
+}
+
+fn lay_out(font: &Font, paragraphs: &[&str], text_box: &TextBox, size: f32) -> FittedText {
+    let scale = Scale::uniform(size);
+    let lines = if text_box.wrap {
+        paragraphs
+            .iter()
+            .flat_map(|paragraph| wrap(font, paragraph, scale, text_box.width))
+            .collect()
+    } else {
+        paragraphs.iter().map(|line| line.to_string()).collect()
+    };
+    FittedText {
+        scale,
+        lines,
+        line_height: line_height(font, text_box, size),
+    }
+}
+
+fn fits(font: &Font, fitted: &FittedText, text_box: &TextBox) -> bool {
+    let tall_enough = text_box
+        .max_height
+        .is_none_or(|max| fitted.lines.len() as u32 * fitted.line_height <= max);
+    tall_enough
+        && fitted
+            .lines
+            .iter()
+            .all(|line| measure_width(font, line, fitted.scale) <= text_box.width)
+}
+
+/// Split one paragraph into lines no wider than `max_width`. A blank paragraph stays a
+/// blank line; a single word wider than the box gets a line of its own
+fn wrap(font: &Font, paragraph: &str, scale: Scale, max_width: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current_line = String::new();
+
+    for word in paragraph.split_whitespace() {
+        let test_line = if current_line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current_line, word)
+        };
+
+        if measure_width(font, &test_line, scale) > max_width && !current_line.is_empty() {
+            lines.push(current_line);
+            current_line = word.to_string();
+        } else {
+            current_line = test_line;
+        }
+    }
+
+    lines.push(current_line);
+    lines
+}
+
+/// Drop the lines below the box and shorten the ones wider than it, marking each cut
+/// with an ellipsis
+fn truncate(font: &Font, mut fitted: FittedText, text_box: &TextBox) -> FittedText {
+    if let Some(max) = text_box.max_height {
+        let max_lines = (max / fitted.line_height).max(1) as usize;
+        if fitted.lines.len() > max_lines {
+            fitted.lines.truncate(max_lines);
+            let last = fitted.lines.last_mut().expect("at least one line is kept");
+            *last = ellipsize(font, last, fitted.scale, text_box.width, true);
+        }
+    }
+    for line in fitted.lines.iter_mut() {
+        if measure_width(font, line, fitted.scale) > text_box.width {
+            *line = ellipsize(font, line, fitted.scale, text_box.width, false);
+        }
+    }
+    fitted
+}
+
+/// Shorten `line` until it and an ellipsis fit in `max_width`. `always` adds the ellipsis
+/// even when the line fits, for text cut off below it
+fn ellipsize(font: &Font, line: &str, scale: Scale, max_width: u32, always: bool) -> String {
+    if !always && measure_width(font, line, scale) <= max_width {
+        return line.to_string();
+    }
+    let ellipsis = if font.glyph('\u{2026}').id().0 != 0 {
+        ELLIPSIS
+    } else {
+        ASCII_ELLIPSIS
+    };
+
+    let mut kept = line.trim_end().to_string();
+    loop {
+        let candidate = format!("{}{}", kept, ellipsis);
+        if kept.is_empty() || measure_width(font, &candidate, scale) <= max_width {
+            return candidate;
+        }
+        kept.pop();
+        kept.truncate(kept.trim_end().len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fonts::{self, FontStyle};
+    use crate::template_layout::{TextAlign, TextField};
+    use image::Rgb;
+
+    fn text_box(size: f32, width: u32, max_height: Option<u32>, wrap: bool) -> TextBox {
+        TextBox {
+            field: Some(TextField::Story),
+            text: String::new(),
+            font: "fallback".to_string(),
+            style: FontStyle::Regular,
+            size,
+            min_size: None,
+            color: Rgb([0, 0, 0]),
+            align: TextAlign::Center,
+            x: 0,
+            y: 0,
+            width,
+            max_height,
+            wrap,
+            line_height: None,
+        }
+    }
+
+    fn font() -> std::sync::Arc<Font<'static>> {
+        fonts::built_in_only().font_or_fallback("fallback", FontStyle::Regular)
+    }
+
+    #[test]
+    fn test_line_breaks_are_kept() {
+        let font = font();
+        let fitted = fit_text(
+            &font,
+            "WANTED FOR DUELING\nFast draw.\n\nDo not challenge.",
+            &text_box(40.0, 1100, None, true),
+        );
+        assert_eq!(
+            fitted.lines,
+            vec!["WANTED FOR DUELING", "Fast draw.", "", "Do not challenge."]
+        );
+        assert_eq!(fitted.scale, Scale::uniform(40.0));
+
+        // Line height comes from the font, a little more than the size
+        assert!(fitted.line_height >= 40 && fitted.line_height <= 60);
+    }
+
+    #[test]
+    fn test_long_names_shrink_to_fit() {
+        let font = font();
+        let name = "The Extremely Long Named Posse Of Tumbleweed Gulch";
+        let text_box = text_box(80.0, 1200, None, false);
+        let fitted = fit_text(&font, name, &text_box);
+
+        assert_eq!(fitted.lines, vec![name]);
+        assert!(fitted.scale.y < 80.0);
+        assert!(fitted.scale.y >= text_box.min_size());
+        assert!(measure_width(&font, name, fitted.scale) <= 1200);
+    }
+
+    #[test]
+    fn test_text_that_never_fits_ends_with_an_ellipsis() {
+        let font = font();
+        let story = "Wanted for rustling. ".repeat(40);
+        let text_box = TextBox {
+            min_size: Some(30.0),
+            ..text_box(30.0, 600, Some(120), true)
+        };
+        let fitted = fit_text(&font, &story, &text_box);
+
+        assert!(fitted.lines.len() as u32 * fitted.line_height <= 120);
+        assert!(fitted.lines.last().unwrap().ends_with('\u{2026}'));
+        for line in &fitted.lines {
+            assert!(measure_width(&font, line, fitted.scale) <= 600);
+        }
+    }
+}